@@ -0,0 +1,134 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::backend::find_executable;
+use crate::Result;
+use std::env;
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Well-known askpass helpers shipped by the desktop environments and OpenSSH
+const HELPERS: [&str; 5] = [
+    "ssh-askpass",
+    "ksshaskpass",
+    "lxqt-openssh-askpass",
+    "x11-ssh-askpass",
+    "gnome-ssh-askpass",
+];
+
+/// Directories where distributions install the askpass helpers out of `PATH`
+const HELPER_DIRS: [&str; 3] = [
+    "/usr/lib/ssh",
+    "/usr/lib/openssh",
+    "/usr/libexec/openssh",
+];
+
+/// Dialog programs the built-in helper asks for the password with
+const DIALOGS: [&str; 2] = ["zenity", "kdialog"];
+
+/// The built-in helper, sudo passes the prompt as the first argument
+const BUILTIN: &str = r#"#!/bin/sh
+if command -v zenity >/dev/null 2>&1; then
+    exec zenity --entry --hide-text --title="Authentication Required" --text="$1"
+fi
+if command -v kdialog >/dev/null 2>&1; then
+    exec kdialog --title "Authentication Required" --password "$1"
+fi
+exit 1
+"#;
+
+/// The graphical helper `sudo -A` runs through `SUDO_ASKPASS` to ask for the password
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Askpass {
+    /// Use `SUDO_ASKPASS` of the current process if set, or else the first helper found
+    /// among `ssh-askpass`, `ksshaskpass`, `lxqt-openssh-askpass`, `x11-ssh-askpass` and
+    /// `gnome-ssh-askpass`, or else a built-in one asking through `zenity` or `kdialog`
+    #[default]
+    Auto,
+    /// Use the helper at the given path
+    Program(PathBuf),
+}
+
+impl Askpass {
+    /// Locate the helper, writing the built-in one out if necessary
+    ///
+    /// Return `None` if no helper could be found
+    pub(crate) fn locate(&self) -> Result<Option<PathBuf>> {
+        match self {
+            Askpass::Program(program) => Ok(Some(program.clone())),
+            Askpass::Auto => {
+                if let Some(program) = Self::find() {
+                    return Ok(Some(program));
+                }
+                if DIALOGS.iter().any(|dialog| find_executable(dialog).is_some()) {
+                    return Ok(Some(builtin()?));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Check whether a helper could be found without writing anything out
+    pub(crate) fn is_available(&self) -> bool {
+        match self {
+            Askpass::Program(program) => program.is_file(),
+            Askpass::Auto => {
+                Self::find().is_some() || DIALOGS.iter().any(|dialog| find_executable(dialog).is_some())
+            }
+        }
+    }
+
+    fn find() -> Option<PathBuf> {
+        if let Some(program) = env::var_os("SUDO_ASKPASS") {
+            let program = PathBuf::from(program);
+            if program.is_file() {
+                return Some(program);
+            }
+        }
+        HELPERS.iter().find_map(|helper| {
+            find_executable(helper).or_else(|| {
+                HELPER_DIRS
+                    .iter()
+                    .map(|dir| Path::new(dir).join(helper))
+                    .find(|program| program.is_file())
+            })
+        })
+    }
+}
+
+/// Write the built-in helper into a directory private to the current user
+fn builtin() -> Result<PathBuf> {
+    let dir = private_dir()?;
+    let program = dir.join("askpass");
+    let temp = dir.join(format!("askpass.{}", std::process::id()));
+    fs::write(&temp, BUILTIN)?;
+    fs::set_permissions(&temp, Permissions::from_mode(0o700))?;
+    fs::rename(&temp, &program)?;
+    Ok(program)
+}
+
+/// Create the directory private to the current user in the temporary directory,
+/// where the files exchanged with the elevated processes are placed
+pub(crate) fn private_dir() -> Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("elevated-command-{}", uid));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            // refuse a directory planted by someone else, who could swap the files to steal secrets
+            let metadata = fs::symlink_metadata(&dir)?;
+            if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o022 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} is not private to the current user", dir.display()),
+                ).into());
+            }
+        }
+        Err(err) => return Err(err.into()),
+    }
+    Ok(dir)
+}
@@ -0,0 +1,211 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::backend;
+use crate::handoff::Handoff;
+use crate::{Backend, Command, Error, Result};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command as TokioCommand};
+
+/// The asynchronous counterparts of `Command::output` and `Command::spawn`,
+/// available with the `tokio` feature
+impl Command {
+    /// Prompting the user with a graphical OS dialog for the root password,
+    /// excuting the command with escalated privileges, and return the output
+    /// without blocking the runtime while the dialog is open
+    ///
+    /// Dropping the future kills the backend, which dismisses a pending dialog.
+    /// The elevated program is only torn down if it runs behind the supervisor,
+    /// see `Command::supervise`, or else it keeps running once it has started,
+    /// as the current process is usually not allowed to signal it
+    ///
+    /// The backends authenticating through stdio, e.g. `Sudo::password`, and
+    /// `Command::pty` are not supported, for which `Error::InvalidArgument` is returned
+    ///
+    /// This method is only applicable on `Linux` with the `tokio` feature
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Direct;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let mut cmd = StdCommand::new("echo");
+    ///     cmd.arg("hello");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new());
+    ///     let output = elevated_cmd.output_async().await.unwrap();
+    ///     assert_eq!(output.stdout, b"hello\n");
+    /// }
+    /// ```
+    pub async fn output_async(&self) -> Result<Output> {
+        let mut child = self.launch_async(Stdio::null(), Stdio::piped(), Stdio::piped())?;
+        drop(child.stdin.take());
+        child.wait_with_output().await
+    }
+
+    /// Prompting the user with a graphical OS dialog for the root password,
+    /// executing the command with escalated privileges as a child process,
+    /// and return an asynchronous handle to it
+    ///
+    /// The stdio handles are configured through `Command::stdin`, `Command::stdout`
+    /// and `Command::stderr`, they are inherited from the parent by default.
+    /// Dropping the handle kills the backend, but only tears the elevated program down
+    /// if it is supervised, see `Command::output_async`
+    ///
+    /// This method is only applicable on `Linux` with the `tokio` feature
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Direct;
+    /// use std::process::{Command as StdCommand, Stdio};
+    /// use tokio::io::AsyncReadExt;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() {
+    ///     let mut cmd = StdCommand::new("echo");
+    ///     cmd.arg("hello");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new()).stdout(Stdio::piped());
+    ///     let mut child = elevated_cmd.spawn_async().unwrap();
+    ///     let mut stdout = String::new();
+    ///     child.stdout.take().unwrap().read_to_string(&mut stdout).await.unwrap();
+    ///     assert_eq!(stdout, "hello\n");
+    ///     assert!(child.wait().await.unwrap().success());
+    /// }
+    /// ```
+    pub fn spawn_async(&mut self) -> Result<AsyncElevatedChild> {
+        let stdin = self.stdin.take().unwrap_or_else(Stdio::inherit);
+        let stdout = self.stdout.take().unwrap_or_else(Stdio::inherit);
+        let stderr = self.stderr.take().unwrap_or_else(Stdio::inherit);
+        self.launch_async(stdin, stdout, stderr)
+    }
+
+    fn launch_async(&self, stdin: Stdio, stdout: Stdio, stderr: Stdio) -> Result<AsyncElevatedChild> {
+        if self.pty {
+            return Err(Error::InvalidArgument(
+                "a pseudo-terminal is not supported asynchronously".to_string(),
+            ));
+        }
+        // dropping the handle closes the control pipe, if any, which tears the supervised
+        // elevated program down, while killing the backend alone might not stop it
+        let (backend, command, handoff) = self.prepare(stdin, stdout, stderr)?;
+        if backend.authenticates_through_stdio() {
+            return Err(Error::InvalidArgument(format!(
+                "{} authenticates through stdio, which is not supported asynchronously",
+                backend.name()
+            )));
+        }
+        let mut command = TokioCommand::from(command);
+        command.kill_on_drop(true);
+        Ok(AsyncElevatedChild::new(command.spawn()?, backend, handoff))
+    }
+}
+
+/// Representation of a running or exited elevated child process,
+/// the asynchronous counterpart of `ElevatedChild` returned by `Command::spawn_async`
+///
+/// The backend is killed when the handle is dropped, but the elevated program
+/// is only torn down if it runs behind the supervisor, see `Command::supervise`
+pub struct AsyncElevatedChild {
+    /// The handle for writing to the child's standard input, if it has been captured
+    pub stdin: Option<ChildStdin>,
+    /// The handle for reading from the child's standard output, if it has been captured
+    pub stdout: Option<ChildStdout>,
+    /// The handle for reading from the child's standard error, if it has been captured
+    pub stderr: Option<ChildStderr>,
+    child: Child,
+    backend: Arc<dyn Backend>,
+    handoff: Handoff,
+}
+
+impl AsyncElevatedChild {
+    fn new(mut child: Child, backend: Arc<dyn Backend>, handoff: Handoff) -> Self {
+        Self {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take(),
+            stderr: child.stderr.take(),
+            child,
+            backend,
+            handoff,
+        }
+    }
+
+    /// Returns the OS-assigned process identifier of the backend process,
+    /// `None` once it has been polled to completion
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Waits for the child to exit completely, returning the status that it exited with
+    ///
+    /// The stdin handle to the child process, if any, will be closed before waiting
+    pub async fn wait(&mut self) -> Result<ExitStatus> {
+        drop(self.stdin.take());
+        let status = self.child.wait().await?;
+        self.check(status)
+    }
+
+    /// Attempts to collect the exit status of the child if it has already exited
+    ///
+    /// Return `None` if the child is still running
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        match self.child.try_wait()? {
+            Some(status) => self.check(status).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Send `signal` to the elevated program, see `ElevatedChild::signal`
+    pub fn signal(&mut self, signal: i32) -> Result<()> {
+        if let Some(ref mut control) = self.handoff.control {
+            return control.send(signal);
+        }
+        let pid = self.child.id().ok_or_else(|| Error::InvalidArgument("the elevated program has exited".to_string()))?;
+        if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Forces the child process to exit and waits for it
+    ///
+    /// The elevated program is killed through its supervisor if any, and the backend directly
+    pub async fn kill(&mut self) -> Result<()> {
+        if let Some(ref mut control) = self.handoff.control {
+            control.send(libc::SIGKILL)?;
+        }
+        let _ = self.child.start_kill();
+        self.child.wait().await?;
+        Ok(())
+    }
+
+    /// Simultaneously waits for the child to exit and collect all remaining
+    /// output on the stdout/stderr handles, returning an `Output` instance
+    pub async fn wait_with_output(mut self) -> Result<Output> {
+        self.child.stdin = self.stdin.take();
+        self.child.stdout = self.stdout.take();
+        self.child.stderr = self.stderr.take();
+        let backend = self.backend;
+        let output = self.child.wait_with_output().await?;
+        match backend::output_failure(backend.as_ref(), &output) {
+            Some(err) => Err(err),
+            None => Ok(output),
+        }
+    }
+
+    fn check(&self, status: ExitStatus) -> Result<ExitStatus> {
+        match self.backend.failure(&status, &[]) {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+}
@@ -0,0 +1,1400 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+//! Elevation backends used on Linux
+//!
+//! A backend is the privileged program that actually escalates the privileges,
+//! e.g. `pkexec` or `sudo`. By default `Command` probes the host and picks the first
+//! available backend able to prompt the user in the `PromptMode` of the command.
+//!
+//! With a graphical prompt, the order is:
+//!
+//! * [`Pkexec`]
+//! * [`Run0`] (only when systemd is running)
+//! * [`Sudo`] with a graphical [`Askpass`] helper
+//! * [`Doas`] (only when `doas.conf` exists)
+//! * [`Sudo`]
+//! * [`Su`]
+//!
+//! With a prompt on the terminal, the order is:
+//!
+//! * [`Doas`] (only when `doas.conf` exists)
+//! * [`Sudo`]
+//! * [`Run0`] (only when systemd is running)
+//! * [`Pkexec`] with its textual authentication agent
+//! * [`Su`]
+//!
+//! When the command is non-interactive, the order is [`Sudo`], [`Doas`] and [`Run0`],
+//! each of them running in its no-prompt mode. [`Pkexec`] and [`Su`] have none.
+//!
+//! When a group is set by `Command::group`, only [`Sudo`] and [`Run0`] are considered.
+//!
+//! The backend runs the elevated program itself whenever it can, so that the rules scoped
+//! to the program, e.g. a `NOPASSWD: /usr/bin/apt` rule of sudoers, a `cmd` rule of doas
+//! or a polkit action with an `exec.path`, apply to it. Otherwise it runs a wrapper,
+//! which the rules see instead:
+//!
+//! * `env`, when variables are set or removed, e.g. `DISPLAY`, `XAUTHORITY` and `HOME`
+//!   forwarded by `EnvPolicy::Explicit`, or when the environment is cleared.
+//!   `EnvPolicy::Allowlist` with no names passes nothing
+//! * `/bin/sh`, which sources the environment file of `EnvPolicy::InheritAll`, or changes
+//!   the working directory for the backends which reset it, i.e. all but [`Run0`]
+//! * `/bin/sh` as the supervisor, see `Command::supervise`
+//!
+//! A specific backend can be forced with `Command::backend`
+pub use crate::askpass::Askpass;
+pub use crate::handoff::Handoff;
+pub use crate::password::PasswordProvider;
+use crate::env::Environment;
+use crate::handoff::{is_shell_name, EnvFile};
+use crate::password::{self, AUTHENTICATED_SENTINEL, PROMPT_SENTINEL};
+use crate::supervisor;
+use crate::{CancellationHandle, Command, EnvPolicy, Error, PromptMode, Result};
+use std::env;
+use std::ffi::{CString, OsStr, OsString};
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as StdCommand, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Written to stderr by the trampoline when it fails to change the working directory
+const CHDIR_FAILURE: &str = "elevated-command: cannot change directory to ";
+
+/// Changes the working directory before replacing itself with the elevated program,
+/// as backends like `pkexec` and `sudo` reset it
+const CHDIR_TRAMPOLINE: &str = r#"cd -- "$1" 2>/dev/null || { echo "elevated-command: cannot change directory to $1" >&2; exit 1; }; shift; exec "$@""#;
+
+/// Sources and removes the environment file before replacing itself with the elevated
+/// program, which is searched through the default `PATH` as `env -i` might have cleared it
+const SOURCE_TRAMPOLINE: &str = r#". "$1" || exit 1; command -p rm -f -- "$1"; shift; exec "$@""#;
+
+/// Variables of the user session forwarded by `EnvPolicy::Explicit`
+const SESSION_VARS: [&str; 3] = ["DISPLAY", "XAUTHORITY", "HOME"];
+
+/// Variables of the graphical session forwarded by `Command::forward_gui_session`,
+/// along with the locale ones
+const GUI_SESSION_VARS: [&str; 10] = [
+    "DISPLAY",
+    "XAUTHORITY",
+    "WAYLAND_DISPLAY",
+    "XDG_RUNTIME_DIR",
+    "XDG_SESSION_TYPE",
+    "XDG_CURRENT_DESKTOP",
+    "DBUS_SESSION_BUS_ADDRESS",
+    "LANG",
+    "LANGUAGE",
+    "LC_ALL",
+];
+
+/// Locations of the configuration of OpenDoas and of the OpenBSD port
+const DOAS_CONFIGS: [&str; 2] = ["/etc/doas.conf", "/usr/local/etc/doas.conf"];
+
+/// Directories searched after `PATH` when locating a backend program,
+/// as `PATH` of background applications is often minimal
+const FALLBACK_DIRS: [&str; 4] = ["/usr/bin", "/bin", "/usr/sbin", "/sbin"];
+
+/// The privileged program used to execute a `Command` with escalated privileges
+pub trait Backend: Send + Sync {
+    /// The name of the backend, e.g. `pkexec`
+    fn name(&self) -> &str;
+
+    /// Check whether the backend could be used on the current host
+    fn is_available(&self) -> bool;
+
+    /// Build the std::process::Command which runs `cmd` with escalated privileges
+    ///
+    /// The program, the arguments, the environment variables and the working directory
+    /// are passed as raw bytes, which do not need to be valid UTF-8
+    ///
+    /// The files the elevated side reads, like the environment file, are added to `handoff`,
+    /// which has to be kept until the command has exited
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Backend, Command};
+    /// use elevated_command::backend::{Handoff, Sudo};
+    /// use std::ffi::{OsStr, OsString};
+    /// use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     // "caf\xe9" is "café" in Latin-1, which is not valid UTF-8
+    ///     let latin1 = |s: &[u8]| OsString::from_vec(s.to_vec());
+    ///     let mut cmd = StdCommand::new(latin1(b"/opt/caf\xe9/bin/tool"));
+    ///     cmd.arg(latin1(b"--name=caf\xe9"));
+    ///     let elevated_cmd = Command::new(cmd);
+    ///
+    ///     let sudo = Sudo::new().program("/bin/sh");
+    ///     let command = sudo.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+    ///     let args: Vec<&OsStr> = command.get_args().collect();
+    ///     assert_eq!(args[args.len() - 2], OsStr::from_bytes(b"/opt/caf\xe9/bin/tool"));
+    ///     assert_eq!(args[args.len() - 1], OsStr::from_bytes(b"--name=caf\xe9"));
+    /// }
+    /// ```
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand>;
+
+    /// Recognize an elevation failure from the exit status of the backend and its stderr,
+    /// the stderr is empty if it has not been captured
+    ///
+    /// Return `None` if the status is the one of the elevated program
+    fn failure(&self, _status: &ExitStatus, _stderr: &[u8]) -> Option<Error> {
+        None
+    }
+
+    /// Whether the backend can run the command with the group set by `Command::group`
+    fn supports_group(&self) -> bool {
+        false
+    }
+
+    /// Whether the backend authenticates through the stdin and stderr of the child,
+    /// which are then always piped whatever the configuration of `Command` is
+    fn authenticates_through_stdio(&self) -> bool {
+        false
+    }
+
+    /// Authenticate with the spawned backend before the child is handed to the caller
+    ///
+    /// The backend is to be killed and reaped, and `Error::TimedOut` or `Error::Cancelled`
+    /// returned, once `deadline` has passed or `cancellation` is cancelled, see
+    /// `Command::timeout` and `Command::cancellation`
+    fn authenticate(
+        &self,
+        _child: &mut Child,
+        _deadline: Option<Instant>,
+        _cancellation: Option<&CancellationHandle>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Elevate through polkit's `pkexec`, prompting with the graphical authentication agent,
+/// or with its textual one on the terminal when the `PromptMode` is `Tty`
+#[derive(Debug, Clone, Default)]
+pub struct Pkexec {
+    program: Option<PathBuf>,
+}
+
+impl Pkexec {
+    /// Constructs a new `Pkexec` backend, `pkexec` is located through `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `pkexec` at the given path instead of the one found in `PATH`
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+}
+
+impl Backend for Pkexec {
+    fn name(&self) -> &str {
+        "pkexec"
+    }
+
+    fn is_available(&self) -> bool {
+        locate(&self.program, "pkexec").is_some()
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        // a registered authentication agent prompts whatever the options are
+        if cmd.non_interactive {
+            return Err(Error::WouldPrompt);
+        }
+        let mut command = StdCommand::new(backend_program(&self.program, "pkexec")?);
+        // the internal agent prompts on the terminal when no graphical agent is registered
+        if cmd.prompt_mode.resolve() == PromptMode::Gui {
+            command.arg("--disable-internal-agent");
+        }
+        if let Some(user) = target_user(cmd, handoff)? {
+            command.args(["--user", user]);
+        }
+        command.args(target_args(cmd, handoff)?);
+        Ok(command)
+    }
+
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        match status.code() {
+            Some(126) => Some(Error::Cancelled),
+            Some(127) if String::from_utf8_lossy(stderr).contains("No authentication agent found") => {
+                Some(Error::WouldPrompt)
+            }
+            Some(127) => Some(Error::NotAuthorized),
+            _ => None,
+        }
+    }
+}
+
+/// Elevate through `sudo`, prompting on the terminal, or with a graphical
+/// askpass helper if one is set by `Sudo::askpass`, or with the password
+/// supplied by the application if a provider is set by `Sudo::password`
+#[derive(Clone, Default)]
+pub struct Sudo {
+    program: Option<PathBuf>,
+    askpass: Option<Askpass>,
+    password: Option<Arc<dyn PasswordProvider>>,
+}
+
+impl fmt::Debug for Sudo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sudo")
+            .field("program", &self.program)
+            .field("askpass", &self.askpass)
+            .field("password", &self.password.is_some())
+            .finish()
+    }
+}
+
+impl Sudo {
+    /// Constructs a new `Sudo` backend, `sudo` is located through `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `sudo` at the given path instead of the one found in `PATH`
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Check whether `sudo` would run a command as root without prompting,
+    /// i.e. the credentials are cached or the rule matching the user is `NOPASSWD`,
+    /// through `sudo -n true`
+    ///
+    /// sudo might log the check as an authentication failure, or report it by mail,
+    /// when the user is not allowed to run `true`, so it is not part of `Command::can_elevate`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::backend::Sudo;
+    ///
+    /// fn main() {
+    ///     let cached = Sudo::new().probe();
+    /// }
+    /// ```
+    pub fn probe(&self) -> bool {
+        locate(&self.program, "sudo").is_some_and(|program| runs_without_prompt(&program))
+    }
+
+    /// Ask for the password with a graphical helper through `sudo -A`,
+    /// which works without a polkit agent
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::{Askpass, Sudo};
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Sudo::new().askpass(Askpass::Program("/usr/bin/ssh-askpass".into())));
+    ///     let output = elevated_cmd.output().unwrap();
+    /// }
+    /// ```
+    pub fn askpass(mut self, askpass: Askpass) -> Self {
+        self.askpass = Some(askpass);
+        self
+    }
+
+    /// Feed `sudo -S` with the password supplied by `provider`, which is asked
+    /// each time `sudo` prompts, so that the application could render its own dialog.
+    /// It takes precedence over `Sudo::askpass`
+    ///
+    /// The stdin and stderr of the elevated program are always piped, as `sudo` reads
+    /// the password from stdin and prompts on stderr
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Sudo;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn ask_password(attempt: u32) -> Option<String> {
+    ///     // render the dialog of the application, return None if the user cancelled it
+    ///     unimplemented!()
+    /// }
+    ///
+    /// fn main() {
+    ///     let cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Sudo::new().password(ask_password));
+    ///     let output = elevated_cmd.output().unwrap();
+    /// }
+    /// ```
+    pub fn password<P: PasswordProvider + 'static>(mut self, provider: P) -> Self {
+        self.password = Some(Arc::new(provider));
+        self
+    }
+}
+
+impl Backend for Sudo {
+    fn name(&self) -> &str {
+        "sudo"
+    }
+
+    fn is_available(&self) -> bool {
+        locate(&self.program, "sudo").is_some()
+            && self.askpass.as_ref().map_or(true, Askpass::is_available)
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        let mut command = StdCommand::new(backend_program(&self.program, "sudo")?);
+        if cmd.non_interactive {
+            command.arg("-n");
+        }
+        if let Some(user) = target_user(cmd, handoff)? {
+            command.args(["-u", user]);
+        }
+        if let Some(group) = target_group(cmd, handoff)? {
+            command.args(["-g", group]);
+        }
+        if self.password.is_some() {
+            command.args(["-S", "-p", PROMPT_SENTINEL, "--"]);
+            // report the end of the authentication, as the prompt might never show
+            // when the credentials are cached
+            command.args(["/bin/sh", "-c", r#"echo "$0" >&2; exec "$@""#, AUTHENTICATED_SENTINEL]);
+            command.args(target_args(cmd, handoff)?);
+            return Ok(command);
+        }
+        if let (Some(ref askpass), false) = (&self.askpass, cmd.non_interactive) {
+            let helper = askpass
+                .locate()?
+                .ok_or(Error::Backend("no askpass helper found".to_string()))?;
+            command.env("SUDO_ASKPASS", helper);
+            command.arg("-A");
+        }
+        command.arg("--");
+        command.args(target_args(cmd, handoff)?);
+        Ok(command)
+    }
+
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        Messages {
+            prefix: "sudo: ",
+            cancelled: &["no password was provided"],
+            not_authorized: &[
+                "incorrect password attempt",
+                "is not in the sudoers file",
+                "is not allowed to execute",
+            ],
+            // sudo has neither a terminal nor an askpass helper to prompt with
+            would_prompt: &["a password is required", "a terminal is required"],
+        }.failure(status, stderr)
+    }
+
+    fn supports_group(&self) -> bool {
+        true
+    }
+
+    fn authenticates_through_stdio(&self) -> bool {
+        self.password.is_some()
+    }
+
+    fn authenticate(
+        &self,
+        child: &mut Child,
+        deadline: Option<Instant>,
+        cancellation: Option<&CancellationHandle>,
+    ) -> Result<()> {
+        match self.password {
+            Some(ref provider) => password::authenticate(provider.as_ref(), child, deadline, cancellation),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Elevate through OpenBSD's `doas`, prompting on the terminal
+///
+/// It is only considered available when a `doas.conf` exists, as `doas`
+/// refuses everything without one
+#[derive(Debug, Clone, Default)]
+pub struct Doas {
+    program: Option<PathBuf>,
+}
+
+impl Doas {
+    /// Constructs a new `Doas` backend, `doas` is located through `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `doas` at the given path instead of the one found in `PATH`
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Check whether `doas` would run a command as root without prompting,
+    /// i.e. the rule matching the user is `nopass` or the credentials persist,
+    /// through `doas -n true`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::backend::Doas;
+    ///
+    /// fn main() {
+    ///     let prompts = !Doas::new().probe();
+    /// }
+    /// ```
+    pub fn probe(&self) -> bool {
+        locate(&self.program, "doas").is_some_and(|program| runs_without_prompt(&program))
+    }
+}
+
+impl Backend for Doas {
+    fn name(&self) -> &str {
+        "doas"
+    }
+
+    fn is_available(&self) -> bool {
+        locate(&self.program, "doas").is_some()
+            && DOAS_CONFIGS.iter().any(|config| Path::new(config).exists())
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        let mut command = StdCommand::new(backend_program(&self.program, "doas")?);
+        if cmd.non_interactive {
+            command.arg("-n");
+        }
+        if let Some(user) = target_user(cmd, handoff)? {
+            command.args(["-u", user]);
+        }
+        command.arg("--");
+        command.args(target_args(cmd, handoff)?);
+        Ok(command)
+    }
+
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        Messages {
+            prefix: "doas: ",
+            not_authorized: &["Authentication failed", "Operation not permitted"],
+            would_prompt: &["Authentication required", "Authorization required"],
+            ..Default::default()
+        }.failure(status, stderr)
+    }
+}
+
+/// Elevate through systemd's `run0`, which asks polkit for authorization
+/// and runs the command in a transient service unit
+///
+/// The environment variables and the working directory are passed through
+/// `--setenv` and `--chdir`, as the unit is started by the service manager
+/// rather than by `run0` itself. The variables removed from the environment of
+/// the unit, or all of them with `EnvPolicy::Clear`, are unset by `env` in front
+/// of the program, which then takes the variables to set on its command line
+#[derive(Debug, Clone, Default)]
+pub struct Run0 {
+    program: Option<PathBuf>,
+    unit: Option<String>,
+    last_unit: Arc<Mutex<Option<String>>>,
+}
+
+impl Run0 {
+    /// Constructs a new `Run0` backend, `run0` is located through `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `run0` at the given path instead of the one found in `PATH`
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Name the transient unit running the command, a unique name prefixed
+    /// with `elevated-command-` is generated for each command otherwise
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// The name of the transient unit of the latest command built by the backend,
+    /// which could be inspected with `systemctl status` while it is running
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Run0;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let run0 = Run0::new();
+    ///     let mut elevated_cmd = Command::new(StdCommand::new("path to the application"));
+    ///     elevated_cmd.backend(run0.clone());
+    ///     let child = elevated_cmd.spawn().unwrap();
+    ///     let unit = run0.unit_name().unwrap();
+    /// }
+    /// ```
+    pub fn unit_name(&self) -> Option<String> {
+        self.last_unit.lock().ok()?.clone()
+    }
+}
+
+impl Backend for Run0 {
+    fn name(&self) -> &str {
+        "run0"
+    }
+
+    fn is_available(&self) -> bool {
+        // run0 talks to the service manager, which only exists when systemd is the init
+        Path::new("/run/systemd/system").is_dir() && locate(&self.program, "run0").is_some()
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        let mut command = StdCommand::new(backend_program(&self.program, "run0")?);
+
+        let unit = match self.unit {
+            Some(ref unit) => unit.clone(),
+            None => {
+                static UNITS: AtomicUsize = AtomicUsize::new(0);
+                let count = UNITS.fetch_add(1, Ordering::Relaxed);
+                format!("elevated-command-{}-{}", std::process::id(), count)
+            }
+        };
+        command.arg(format!("--unit={}", unit));
+        if cmd.non_interactive {
+            command.arg("--no-ask-password");
+        }
+        if let Some(user) = target_user(cmd, handoff)? {
+            command.arg(format!("--user={}", user));
+        }
+        if let Some(group) = target_group(cmd, handoff)? {
+            command.arg(format!("--group={}", group));
+        }
+        if let Ok(mut last_unit) = self.last_unit.lock() {
+            *last_unit = Some(unit);
+        }
+
+        // a variable named alone takes its value from the environment of run0, off its
+        // command line, while the environment of the service manager the unit starts with
+        // is cleared or stripped by `env`, which would clear the variables set as well
+        let environment = Environment::new(cmd, &SESSION_VARS, &gui_session_vars(cmd));
+        let (assignable, unassignable): (Vec<_>, Vec<_>) =
+            environment.vars.into_iter().partition(|(k, _)| is_shell_name(k));
+        let mut args = vec![];
+        if environment.clear || !environment.removed.is_empty() {
+            let env = system_program("env").ok_or(Error::Backend("env not found".to_string()))?;
+            args.push(env.into_os_string());
+            if environment.clear {
+                args.push("-i".into());
+            }
+            for k in environment.removed {
+                args.push("-u".into());
+                args.push(k);
+            }
+        }
+        let (passed, assignable) = match environment.clear {
+            true => (assignable, vec![]),
+            false => (vec![], assignable),
+        };
+        args.extend(env_pairs(passed)?);
+        for (k, v) in assignable {
+            let mut arg = OsString::from("--setenv=");
+            arg.push(&k);
+            command.arg(arg).env(k, v);
+        }
+        if let Some(dir) = current_dir(cmd)? {
+            let mut arg = OsString::from("--chdir=");
+            arg.push(dir);
+            command.arg(arg);
+        }
+
+        command.arg("--");
+        args.extend(runuser_args(cmd, handoff)?);
+        args.extend(unassignable_env(unassignable)?);
+        args.push(target_program(cmd)?);
+        args.extend(cmd.cmd.get_args().map(OsStr::to_os_string));
+        command.args(supervised(handoff, args));
+        Ok(command)
+    }
+
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        Messages {
+            not_authorized: &["Failed to start transient service unit: Access denied"],
+            would_prompt: &["Interactive authentication required"],
+            ..Default::default()
+        }.failure(status, stderr)
+    }
+
+    fn supports_group(&self) -> bool {
+        true
+    }
+}
+
+/// Elevate through `su`, prompting for the password of the target user on the terminal
+#[derive(Debug, Clone, Default)]
+pub struct Su {
+    program: Option<PathBuf>,
+}
+
+impl Su {
+    /// Constructs a new `Su` backend, `su` is located through `PATH`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the `su` at the given path instead of the one found in `PATH`
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+}
+
+impl Backend for Su {
+    fn name(&self) -> &str {
+        "su"
+    }
+
+    fn is_available(&self) -> bool {
+        locate(&self.program, "su").is_some()
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        // su always asks for the password of the target user
+        if cmd.non_interactive {
+            return Err(Error::WouldPrompt);
+        }
+        let user = target_user(cmd, handoff)?.unwrap_or("root");
+        let mut command = StdCommand::new(backend_program(&self.program, "su")?);
+        // su only takes a command string, so let the shell exec the arguments following it,
+        // which avoids quoting them. `--` keeps su from parsing options among the arguments
+        command.args(["-s", "/bin/sh", "-c", r#"exec "$0" "$@""#, "--", user]);
+        command.args(target_args(cmd, handoff)?);
+        Ok(command)
+    }
+
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        Messages {
+            prefix: "su: ",
+            not_authorized: &["Authentication failure", "incorrect password"],
+            ..Default::default()
+        }.failure(status, stderr)
+    }
+}
+
+/// Run the command directly, without escalating privileges
+///
+/// It is never picked by the detection, and is meant to test the code built
+/// upon `Command` or `ElevatedSession` without prompting
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Direct;
+
+impl Direct {
+    /// Constructs a new `Direct` backend
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Backend for Direct {
+    fn name(&self) -> &str {
+        "direct"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: &Command, handoff: &mut Handoff) -> Result<StdCommand> {
+        let args = target_args(cmd, handoff)?;
+        let mut command = StdCommand::new(&args[0]);
+        command.args(&args[1..]);
+        Ok(command)
+    }
+}
+
+/// Probe the host for the first available backend able to run `cmd`,
+/// i.e. to prompt the user in its `PromptMode`, or not to prompt at all
+/// if it is non-interactive
+///
+/// Return `None` if none of the supported backends could be used
+pub fn detect(cmd: &Command) -> Option<Box<dyn Backend>> {
+    let candidates: Vec<Box<dyn Backend>> = if cmd.non_interactive {
+        vec![Box::new(Sudo::new()), Box::new(Doas::new()), Box::new(Run0::new())]
+    } else if cmd.prompt_mode.resolve() == PromptMode::Tty {
+        vec![
+            Box::new(Doas::new()),
+            Box::new(Sudo::new()),
+            Box::new(Run0::new()),
+            Box::new(Pkexec::new()),
+            Box::new(Su::new()),
+        ]
+    } else {
+        vec![
+            Box::new(Pkexec::new()),
+            Box::new(Run0::new()),
+            Box::new(Sudo::new().askpass(Askpass::Auto)),
+            // the ones prompting on the terminal are the last resort
+            Box::new(Doas::new()),
+            Box::new(Sudo::new()),
+            Box::new(Su::new()),
+        ]
+    };
+    candidates
+        .into_iter()
+        .filter(|backend| cmd.group.is_none() || backend.supports_group())
+        .find(|backend| backend.is_available())
+}
+
+/// Whether the current process runs in a graphical session
+pub(crate) fn is_graphical_session() -> bool {
+    env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+/// Search `PATH` and the fallback directories for an executable file named `name`
+pub(crate) fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .chain(FALLBACK_DIRS.iter().map(PathBuf::from))
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Search the fallback directories only for an executable file named `name`,
+/// for the programs run after the environment might have been cleared
+fn system_program(name: &str) -> Option<PathBuf> {
+    FALLBACK_DIRS
+        .iter()
+        .map(|dir| Path::new(dir).join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Search the directories of `path` for an executable file named `name`
+fn search_path(name: &OsStr, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn locate(program: &Option<PathBuf>, name: &str) -> Option<PathBuf> {
+    match program {
+        Some(program) => is_executable(program).then(|| program.clone()),
+        None => find_executable(name),
+    }
+}
+
+/// The target user set by `Command::user`, checked to exist through `getpwnam`
+fn target_user<'a>(cmd: &'a Command, handoff: &Handoff) -> Result<Option<&'a str>> {
+    match cmd.user {
+        Some(ref user) if !lookup(user, |name, buf, len| {
+            let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result: *mut libc::passwd = std::ptr::null_mut();
+            let err = unsafe { libc::getpwnam_r(name, &mut passwd, buf, len, &mut result) };
+            (err, !result.is_null())
+        }) => Err(Error::UserNotFound(user.clone())),
+        _ if switched_by_supervisor(cmd, handoff) => Ok(None),
+        ref user => Ok(user.as_deref()),
+    }
+}
+
+/// The target group set by `Command::group`, checked to exist through `getgrnam`
+fn target_group<'a>(cmd: &'a Command, handoff: &Handoff) -> Result<Option<&'a str>> {
+    match cmd.group {
+        Some(ref group) if !lookup(group, |name, buf, len| {
+            let mut grp: libc::group = unsafe { std::mem::zeroed() };
+            let mut result: *mut libc::group = std::ptr::null_mut();
+            let err = unsafe { libc::getgrnam_r(name, &mut grp, buf, len, &mut result) };
+            (err, !result.is_null())
+        }) => Err(Error::GroupNotFound(group.clone())),
+        _ if switched_by_supervisor(cmd, handoff) => Ok(None),
+        ref group => Ok(group.as_deref()),
+    }
+}
+
+/// Whether the target user is switched to by `runuser` behind the supervisor rather than
+/// by the backend, which then runs the supervisor as root, as only root could open its
+/// control pipe in the directory private to the current user
+fn switched_by_supervisor(cmd: &Command, handoff: &Handoff) -> bool {
+    handoff.control.is_some() && cmd.user.as_deref().is_some_and(|user| user != "root")
+}
+
+/// Whether the entry named `name` exists, through a reentrant lookup function
+/// which is retried with a larger buffer while it reports `ERANGE`
+fn lookup<F>(name: &str, lookup_r: F) -> bool
+where
+    F: Fn(*const libc::c_char, *mut libc::c_char, usize) -> (libc::c_int, bool),
+{
+    let name = match CString::new(name) {
+        Ok(name) => name,
+        Err(_) => return false,
+    };
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        match lookup_r(name.as_ptr(), buf.as_mut_ptr(), buf.len()) {
+            (libc::ERANGE, _) => buf.resize(buf.len() * 2, 0),
+            (_, found) => return found,
+        }
+    }
+}
+
+/// Whether `program -n true` succeeds, i.e. the backend runs it as root without prompting
+fn runs_without_prompt(program: &Path) -> bool {
+    StdCommand::new(program)
+        .args(["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn backend_program(program: &Option<PathBuf>, name: &str) -> Result<PathBuf> {
+    locate(program, name).ok_or_else(|| {
+        log::debug!("{} not found", name);
+        Error::BackendNotFound
+    })
+}
+
+/// The messages reported on stderr by backends which exit with `1` on failure,
+/// through lines starting with `prefix`, by the failure they report
+#[derive(Default)]
+struct Messages<'a> {
+    prefix: &'a str,
+    cancelled: &'a [&'a str],
+    not_authorized: &'a [&'a str],
+    would_prompt: &'a [&'a str],
+}
+
+impl Messages<'_> {
+    /// Recognize the failure reported by the backend
+    fn failure(&self, status: &ExitStatus, stderr: &[u8]) -> Option<Error> {
+        if status.code() != Some(1) {
+            return None;
+        }
+        let stderr = String::from_utf8_lossy(stderr);
+        let messages: Vec<&str> = stderr
+            .lines()
+            .filter_map(|line| line.strip_prefix(self.prefix))
+            .collect();
+        let matches = |patterns: &[&str]| {
+            messages.iter().any(|message| patterns.iter().any(|pattern| message.contains(pattern)))
+        };
+        if matches(self.cancelled) {
+            Some(Error::Cancelled)
+        } else if matches(self.would_prompt) {
+            Some(Error::WouldPrompt)
+        } else if matches(self.not_authorized) {
+            Some(Error::NotAuthorized)
+        } else {
+            None
+        }
+    }
+}
+
+/// Build the arguments following the backend program: the supervisor when the command
+/// is launched with a control pipe, the environment variables passed through `env`
+/// and the environment file, `runuser` if it switches to the target user, the trampoline
+/// changing the working directory, the variables the shell cannot assign, the path
+/// to the program and its arguments
+pub(crate) fn target_args(cmd: &Command, handoff: &mut Handoff) -> Result<Vec<OsString>> {
+    let mut args: Vec<OsString> = vec![];
+    let environment = Environment::new(cmd, &SESSION_VARS, &gui_session_vars(cmd));
+    let (assignable, unassignable): (Vec<_>, Vec<_>) =
+        environment.vars.into_iter().partition(|(k, _)| is_shell_name(k));
+    let (sourced, passed): (Vec<_>, Vec<_>) =
+        assignable.into_iter().partition(|_| sources_env_file(cmd, handoff));
+    if environment.clear || !environment.removed.is_empty() || !passed.is_empty() {
+        args.push("env".into());
+        if environment.clear {
+            args.push("-i".into());
+        }
+        for k in environment.removed.iter() {
+            args.push("-u".into());
+            args.push(k.clone());
+        }
+        args.extend(env_pairs(passed)?);
+    }
+
+    if !sourced.is_empty() {
+        let env_file = EnvFile::create(&sourced)?;
+        args.extend(["/bin/sh", "-c", SOURCE_TRAMPOLINE, "elevated-command"].map(OsString::from));
+        args.push(env_file.path().into());
+        handoff.env_file = Some(env_file);
+    }
+
+    args.extend(runuser_args(cmd, handoff)?);
+    if let Some(dir) = current_dir(cmd)? {
+        args.extend(["/bin/sh", "-c", CHDIR_TRAMPOLINE, "elevated-command"].map(OsString::from));
+        args.push(dir.into_os_string());
+    }
+
+    args.extend(unassignable_env(unassignable)?);
+    args.push(target_program(cmd)?);
+    args.extend(cmd.cmd.get_args().map(OsStr::to_os_string));
+    Ok(supervised(handoff, args))
+}
+
+/// The `env` command setting the variables whose names the shell cannot assign, right
+/// before the elevated program, as shells like dash drop them from the environment
+/// of the programs they run, i.e. the trampolines and the supervisor
+fn unassignable_env(vars: Vec<(OsString, OsString)>) -> Result<Vec<OsString>> {
+    if vars.is_empty() {
+        return Ok(vec![]);
+    }
+    // the variables might have been cleared by `env -i`, PATH included
+    let env = system_program("env").ok_or(Error::Backend("env not found".to_string()))?;
+    let mut args = vec![env.into_os_string()];
+    args.extend(env_pairs(vars)?);
+    Ok(args)
+}
+
+/// Whether the variables are passed through an environment file, which is only done
+/// for `EnvPolicy::InheritAll` as the shell sourcing it is the program the backend runs,
+/// and requires the file to be sourced as root, as it is private to the current user
+fn sources_env_file(cmd: &Command, handoff: &Handoff) -> bool {
+    matches!(cmd.env_policy, EnvPolicy::InheritAll)
+        && (!matches!(cmd.user.as_deref(), Some(user) if user != "root") || switched_by_supervisor(cmd, handoff))
+}
+
+/// The `runuser` command switching to the target user behind the supervisor, if any
+fn runuser_args(cmd: &Command, handoff: &Handoff) -> Result<Vec<OsString>> {
+    let user = match cmd.user {
+        Some(ref user) if switched_by_supervisor(cmd, handoff) => user,
+        _ => return Ok(vec![]),
+    };
+    // the variables might have been cleared by `env -i`, PATH included
+    let runuser = system_program("runuser")
+        .ok_or_else(|| Error::CannotSwitchUser(format!("{} without runuser", user)))?;
+    let mut args = vec![runuser.into_os_string()];
+    args.extend(["-m", "-u", user].map(OsString::from));
+    if let Some(ref group) = cmd.group {
+        args.extend(["-g", group].map(OsString::from));
+    }
+    args.push("--".into());
+    Ok(args)
+}
+
+/// Prepend the supervisor to `args` when the command is launched with a control pipe
+fn supervised(handoff: &Handoff, args: Vec<OsString>) -> Vec<OsString> {
+    match handoff.control {
+        Some(ref control) => supervisor::supervise(control.path(), args),
+        None => args,
+    }
+}
+
+/// The program of the wrapped std::process::Command, resolved into an absolute path
+/// unless `Command::resolve_program` is disabled
+///
+/// A program name without `/` is searched through the `PATH` set on the wrapped
+/// std::process::Command, or else the one of the current process, as the backends
+/// either refuse relative names or search the `PATH` of root. A relative path is
+/// resolved against the working directory of the elevated program
+pub(crate) fn target_program(cmd: &Command) -> Result<OsString> {
+    let program = cmd.cmd.get_program();
+    if !cmd.resolve_program {
+        return Ok(program.to_os_string());
+    }
+    if program.as_bytes().contains(&b'/') {
+        let dir = match current_dir(cmd)? {
+            Some(dir) => dir,
+            None => env::current_dir()?,
+        };
+        return Ok(dir.join(program).into_os_string());
+    }
+
+    let path = cmd.cmd
+        .get_envs()
+        .find(|(k, _)| *k == "PATH")
+        .map(|(_, v)| v.map(OsStr::to_os_string).unwrap_or_default())
+        .or_else(|| env::var_os("PATH"))
+        .unwrap_or_default();
+    search_path(program, &path)
+        .map(PathBuf::into_os_string)
+        .ok_or_else(|| Error::ProgramNotFound(program.to_os_string()))
+}
+
+/// The `KEY=VALUE` pairs of the variables to set in the environment
+fn env_pairs(vars: Vec<(OsString, OsString)>) -> Result<Vec<OsString>> {
+    let mut pairs = vec![];
+    for (k, v) in vars {
+        if k.is_empty() || k.as_bytes().contains(&b'=') {
+            return Err(Error::InvalidArgument(format!("invalid key: {:?}", k)));
+        }
+        let mut pair = k;
+        pair.push("=");
+        pair.push(v);
+        pairs.push(pair);
+    }
+    Ok(pairs)
+}
+
+/// The variables of the graphical session present in the current process,
+/// if `Command::forward_gui_session` is enabled
+fn gui_session_vars(cmd: &Command) -> Vec<OsString> {
+    if !cmd.forward_gui_session {
+        return vec![];
+    }
+    let mut vars: Vec<OsString> = GUI_SESSION_VARS.iter().map(OsString::from).collect();
+    vars.extend(env::vars_os().map(|(k, _)| k).filter(|k| k.as_bytes().starts_with(b"LC_")));
+    vars
+}
+
+/// The absolute working directory set on the wrapped std::process::Command
+///
+/// A relative one is resolved against the working directory of the current process
+pub(crate) fn current_dir(cmd: &Command) -> Result<Option<PathBuf>> {
+    let dir = match cmd.cmd.get_current_dir() {
+        Some(dir) => env::current_dir()?.join(dir),
+        None => return Ok(None),
+    };
+    if !dir.is_dir() {
+        return Err(Error::WorkingDirectory(dir));
+    }
+    Ok(Some(dir))
+}
+
+/// Recognize an elevation failure from the captured output of the backend,
+/// including the failure of the trampoline to change the working directory as root
+pub(crate) fn output_failure(backend: &dyn Backend, output: &Output) -> Option<Error> {
+    if let Some(err) = backend.failure(&output.status, &output.stderr) {
+        return Some(err);
+    }
+    chdir_failure(output)
+}
+
+/// Recognize the failure of the trampoline to change the working directory
+pub(crate) fn chdir_failure(output: &Output) -> Option<Error> {
+    if output.status.success() {
+        return None;
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stderr
+        .lines()
+        .find_map(|line| line.strip_prefix(CHDIR_FAILURE))
+        .map(|dir| Error::WorkingDirectory(PathBuf::from(dir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::ffi::OsStringExt;
+    use std::os::unix::fs::symlink;
+
+    /// A fake `sudo` checking the password, which is `secret`, then running the command
+    /// unprivileged, reporting the failures as `sudo` does
+    const FAKE_SUDO: &str = r#"#!/bin/sh
+case $1 in
+    -n)
+        echo "sudo: a password is required" >&2
+        exit 1
+        ;;
+    -A)
+        password=$("$SUDO_ASKPASS" "[sudo] password:")
+        [ -n "$password" ] || { echo "sudo: no password was provided" >&2; exit 1; }
+        [ "$password" = secret ] || { echo "sudo: 1 incorrect password attempt" >&2; exit 1; }
+        shift 2
+        exec "$@"
+        ;;
+    -S)
+        prompt=$3
+        shift 4
+        for attempt in 1 2 3; do
+            printf '%s' "$prompt" >&2
+            read -r password
+            [ "$password" = secret ] && exec "$@"
+            echo "Sorry, try again." >&2
+        done
+        echo "sudo: 3 incorrect password attempts" >&2
+        exit 1
+        ;;
+    *)
+        echo "sudo: a terminal is required to read the password" >&2
+        exit 1
+        ;;
+esac
+"#;
+
+    /// The fake `sudo` installed in a temporary directory, which is run
+    /// by the backend returned by `FakeSudo::backend`
+    struct FakeSudo {
+        dir: TempDir,
+    }
+
+    impl FakeSudo {
+        fn install() -> Self {
+            let sudo = Self { dir: TempDir::new() };
+            sudo.script("sudo", FAKE_SUDO);
+            sudo
+        }
+
+        /// A `Sudo` backend running the fake `sudo`
+        fn backend(&self) -> Sudo {
+            Sudo::new().program(self.dir.0.join("sudo"))
+        }
+
+        /// Write an executable script named `name` next to the fake `sudo`
+        fn script(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.dir.0.join(name);
+            fs::write(&path, content).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        }
+    }
+
+    /// A directory in the temporary directory, whose name is not valid UTF-8,
+    /// removed with its content when it is dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static DIRS: AtomicUsize = AtomicUsize::new(0);
+            let count = DIRS.fetch_add(1, Ordering::Relaxed);
+            let name = format!("elevated-command-{}-{}-", std::process::id(), count);
+            let dir = env::temp_dir().join(latin1(&[name.as_bytes(), b"caf\xe9"].concat()));
+            fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// "caf\xe9" is "café" in Latin-1, which is not valid UTF-8
+    fn latin1(bytes: &[u8]) -> OsString {
+        OsString::from_vec(bytes.to_vec())
+    }
+
+    /// A command whose program, argument, variable and working directory are not valid UTF-8
+    fn latin1_command(dir: &TempDir) -> Command {
+        let mut cmd = StdCommand::new(latin1(b"/opt/caf\xe9/bin/tool"));
+        cmd.arg(latin1(b"--name=caf\xe9"));
+        cmd.env(latin1(b"CAF\xc9"), latin1(b"caf\xe9"));
+        cmd.env("NAME", latin1(b"caf\xe9"));
+        cmd.current_dir(&dir.0);
+        Command::new(cmd)
+    }
+
+    fn args(command: &StdCommand) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    /// Check that the backend passes the program, the argument, the variable
+    /// and the working directory of `latin1_command` through `env` and the trampoline
+    fn assert_passes_latin1(backend: &dyn Backend) {
+        let dir = TempDir::new();
+        let command = backend.command(&latin1_command(&dir), &mut Handoff::new()).unwrap();
+        let args = args(&command);
+        assert!(args.contains(&OsStr::from_bytes(b"CAF\xc9=caf\xe9")), "{}", backend.name());
+        assert!(args.contains(&OsStr::from_bytes(b"NAME=caf\xe9")), "{}", backend.name());
+        assert!(args.contains(&dir.0.as_os_str()), "{}", backend.name());
+        assert_eq!(args[args.len() - 2], OsStr::from_bytes(b"/opt/caf\xe9/bin/tool"));
+        assert_eq!(args[args.len() - 1], OsStr::from_bytes(b"--name=caf\xe9"));
+    }
+
+    #[test]
+    fn passes_latin1_through_pkexec() {
+        assert_passes_latin1(&Pkexec::new().program("/bin/sh"));
+    }
+
+    #[test]
+    fn passes_latin1_through_sudo() {
+        assert_passes_latin1(&Sudo::new().program("/bin/sh"));
+    }
+
+    #[test]
+    fn passes_latin1_through_doas() {
+        assert_passes_latin1(&Doas::new().program("/bin/sh"));
+    }
+
+    #[test]
+    fn passes_latin1_through_direct() {
+        assert_passes_latin1(&Direct::new());
+    }
+
+    #[test]
+    fn passes_latin1_through_run0() {
+        let dir = TempDir::new();
+        let command = Run0::new().program("/bin/sh").command(&latin1_command(&dir), &mut Handoff::new()).unwrap();
+        let args = args(&command);
+        // the value is taken from the environment of run0
+        assert!(args.contains(&OsStr::new("--setenv=NAME")));
+        assert!(command.get_envs().any(|(k, v)| k == "NAME" && v == Some(OsStr::from_bytes(b"caf\xe9"))));
+        assert!(args.contains(&OsStr::from_bytes(b"CAF\xc9=caf\xe9")));
+        let chdir = [b"--chdir=".as_slice(), dir.0.as_os_str().as_bytes()].concat();
+        assert!(args.contains(&OsStr::from_bytes(&chdir)));
+        assert_eq!(args[args.len() - 2], OsStr::from_bytes(b"/opt/caf\xe9/bin/tool"));
+        assert_eq!(args[args.len() - 1], OsStr::from_bytes(b"--name=caf\xe9"));
+    }
+
+    #[test]
+    fn resolves_the_program_through_the_path() {
+        let dir = TempDir::new();
+        let tool = dir.0.join("tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        // the PATH set on the wrapped command comes before the one of the current process
+        let mut cmd = StdCommand::new("tool");
+        cmd.env("PATH", &dir.0);
+        assert_eq!(target_program(&Command::new(cmd)).unwrap(), tool);
+
+        let mut cmd = StdCommand::new("elevated-command-missing");
+        cmd.env("PATH", &dir.0);
+        assert!(matches!(
+            target_program(&Command::new(cmd)),
+            Err(Error::ProgramNotFound(program)) if program == "elevated-command-missing"
+        ));
+    }
+
+    #[test]
+    fn clears_the_environment_of_run0() {
+        let run0 = Run0::new().program("/bin/sh");
+        let mut cmd = StdCommand::new("/usr/bin/tool");
+        cmd.env("NAME", "value").env_remove("LANG");
+        let mut elevated_cmd = Command::new(cmd);
+        elevated_cmd.env_policy(EnvPolicy::Allowlist(vec![]));
+        let command = run0.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+        let run0_args = args(&command);
+        let program = run0_args.iter().position(|arg| *arg == "/usr/bin/tool").unwrap();
+        assert!(run0_args.contains(&OsStr::new("--setenv=NAME")));
+        assert!(run0_args[program - 3].as_bytes().ends_with(b"/env"));
+        assert_eq!(run0_args[program - 2..program], ["-u", "LANG"]);
+
+        // the variables set are cleared along with the others
+        elevated_cmd.env_policy(EnvPolicy::Clear);
+        let command = run0.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+        let run0_args = args(&command);
+        let program = run0_args.iter().position(|arg| *arg == "/usr/bin/tool").unwrap();
+        assert!(!run0_args.contains(&OsStr::new("--setenv=NAME")));
+        assert!(run0_args[program - 5].as_bytes().ends_with(b"/env"));
+        assert_eq!(run0_args[program - 4..program], ["-i", "-u", "LANG", "NAME=value"]);
+    }
+
+    #[test]
+    fn runs_latin1_through_direct() {
+        let dir = TempDir::new();
+        let program = dir.0.join(latin1(b"tool\xe9"));
+        symlink("/bin/sh", &program).unwrap();
+
+        let mut cmd = StdCommand::new(&program);
+        // sh drops the variable it cannot assign from the environment of its children,
+        // so read its own. printf '\311' writes "\xc9", as the script itself is valid UTF-8
+        let script = r#"printf '%s\n' "$0" "$PWD" "$NAME"; tr '\0' '\n' < /proc/$$/environ | grep -a "^$(printf 'CAF\311')=""#;
+        cmd.args(["-c", script]);
+        cmd.arg(latin1(b"caf\xe9"));
+        // a variable passed through `env` or the environment file, and one which sh cannot assign
+        cmd.env("NAME", latin1(b"caf\xe9"));
+        cmd.env(latin1(b"CAF\xc9"), latin1(b"caf\xe9"));
+        cmd.current_dir(&dir.0);
+        let mut elevated_cmd = Command::new(cmd);
+        elevated_cmd.backend(Direct::new());
+
+        let expected = [b"caf\xe9\n".as_slice(), dir.0.as_os_str().as_bytes(), b"\ncaf\xe9\nCAF\xc9=caf\xe9\n"].concat();
+        for env_policy in [EnvPolicy::Explicit, EnvPolicy::InheritAll] {
+            for supervise in [false, true] {
+                let output = elevated_cmd.env_policy(env_policy.clone()).supervise(supervise).output().unwrap();
+                assert!(output.status.success(), "{:?}", output);
+                assert_eq!(output.stdout, expected, "{:?}, supervise: {}", env_policy, supervise);
+            }
+        }
+    }
+
+    #[test]
+    fn runs_the_program_directly() {
+        let sudo = Sudo::new().program("/bin/sh");
+        let mut elevated_cmd = Command::new(StdCommand::new("/usr/bin/apt"));
+        elevated_cmd.env_policy(EnvPolicy::Allowlist(vec![]));
+        let command = sudo.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+        assert_eq!(args(&command), ["--", "/usr/bin/apt"]);
+
+        // an explicit variable needs `env`, but no shell
+        let mut cmd = StdCommand::new("/usr/bin/apt");
+        cmd.env("LANG", "C");
+        let mut elevated_cmd = Command::new(cmd);
+        elevated_cmd.env_policy(EnvPolicy::Allowlist(vec![]));
+        let command = sudo.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+        assert_eq!(args(&command), ["--", "env", "LANG=C", "/usr/bin/apt"]);
+    }
+
+    #[test]
+    fn authenticates_through_askpass() {
+        let sudo = FakeSudo::install();
+        let askpass = sudo.script("askpass", "#!/bin/sh\necho secret\n");
+        let cancelled = sudo.script("cancelled", "#!/bin/sh\nexit 1\n");
+        let wrong = sudo.script("wrong", "#!/bin/sh\necho guess\n");
+
+        let mut elevated_cmd = Command::new(StdCommand::new("true"));
+        elevated_cmd.backend(sudo.backend().askpass(Askpass::Program(askpass)));
+        assert!(elevated_cmd.output().unwrap().status.success());
+
+        elevated_cmd.backend(sudo.backend().askpass(Askpass::Program(cancelled)));
+        assert!(matches!(elevated_cmd.output(), Err(Error::Cancelled)));
+
+        elevated_cmd.backend(sudo.backend().askpass(Askpass::Program(wrong)));
+        assert!(matches!(elevated_cmd.output(), Err(Error::NotAuthorized)));
+    }
+
+    #[test]
+    fn reports_sudo_without_terminal() {
+        let sudo = FakeSudo::install();
+        let mut elevated_cmd = Command::new(StdCommand::new("true"));
+        elevated_cmd.backend(sudo.backend());
+        assert!(matches!(elevated_cmd.output(), Err(Error::WouldPrompt)));
+
+        elevated_cmd.non_interactive(true);
+        assert!(matches!(elevated_cmd.output(), Err(Error::WouldPrompt)));
+    }
+
+    #[test]
+    fn detects_backends_able_to_run_the_command() {
+        let mut elevated_cmd = Command::new(StdCommand::new("true"));
+        elevated_cmd.prompt_mode(PromptMode::Tty);
+        // doas comes first on the terminal
+        if Sudo::new().is_available() && !Doas::new().is_available() {
+            assert_eq!(detect(&elevated_cmd).unwrap().name(), "sudo");
+        }
+
+        elevated_cmd.non_interactive(true);
+        if let Some(backend) = detect(&elevated_cmd) {
+            assert!(["sudo", "doas", "run0"].contains(&backend.name()), "{}", backend.name());
+        }
+
+        elevated_cmd.non_interactive(false).group("root");
+        if let Some(backend) = detect(&elevated_cmd) {
+            assert!(["sudo", "run0"].contains(&backend.name()), "{}", backend.name());
+        }
+    }
+
+    #[test]
+    fn authenticates_with_password() {
+        let sudo = FakeSudo::install();
+        let mut cmd = StdCommand::new("echo");
+        cmd.arg("done");
+        let mut elevated_cmd = Command::new(cmd);
+
+        // the first password is wrong
+        elevated_cmd.backend(sudo.backend().password(|attempt: u32| {
+            Some(if attempt == 1 { "guess".to_string() } else { "secret".to_string() })
+        }));
+        let output = elevated_cmd.output().unwrap();
+        assert_eq!(output.stdout, b"done\n");
+
+        elevated_cmd.backend(sudo.backend().password(|_: u32| None));
+        assert!(matches!(elevated_cmd.output(), Err(Error::Cancelled)));
+
+        elevated_cmd.backend(sudo.backend().password(|_: u32| Some("guess".to_string())));
+        assert!(matches!(elevated_cmd.output(), Err(Error::NotAuthorized)));
+    }
+}
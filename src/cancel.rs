@@ -0,0 +1,76 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Cancels an elevated run from another thread, be it waiting for the user
+/// to authenticate or running the elevated program
+///
+/// It is set on a `Command` by `Command::cancellation`, the clones of a handle
+/// share the same state. Once cancelled, a handle stays cancelled
+///
+/// This struct is only available on `Linux`
+///
+/// # Examples
+///
+/// ```
+/// use elevated_command::{CancellationHandle, Command, Error};
+/// use elevated_command::backend::Direct;
+/// use std::process::Command as StdCommand;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// fn main() {
+///     let handle = CancellationHandle::new();
+///     let mut cmd = StdCommand::new("sleep");
+///     cmd.arg("10");
+///     let mut elevated_cmd = Command::new(cmd);
+///     elevated_cmd.backend(Direct::new()).cancellation(handle.clone());
+///
+///     let canceller = handle.clone();
+///     thread::spawn(move || {
+///         thread::sleep(Duration::from_millis(100));
+///         canceller.cancel();
+///     });
+///     assert!(matches!(elevated_cmd.output(), Err(Error::Cancelled)));
+///     assert!(handle.is_cancelled());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationHandle {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl CancellationHandle {
+    /// Constructs a new `CancellationHandle`, which is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the runs of the commands the handle is set on,
+    /// the backend and the elevated program are terminated
+    pub fn cancel(&self) {
+        let (cancelled, changed) = &*self.state;
+        *cancelled.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        changed.notify_all();
+    }
+
+    /// Whether the handle has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Block until the handle is cancelled or `timeout` elapses,
+    /// return whether it has been cancelled
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        let (cancelled, changed) = &*self.state;
+        let guard = cancelled.lock().unwrap_or_else(|err| err.into_inner());
+        let (guard, _) = changed
+            .wait_timeout_while(guard, timeout, |cancelled| !*cancelled)
+            .unwrap_or_else(|err| err.into_inner());
+        *guard
+    }
+}
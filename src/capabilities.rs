@@ -0,0 +1,124 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::backend::{Backend, Doas, Pkexec, Run0, Su, Sudo};
+use crate::Command;
+use std::ffi::CStr;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Groups granted administrative privileges by the default configurations
+/// of sudo, doas and polkit across distributions
+const PRIVILEGED_GROUPS: [&str; 3] = ["wheel", "sudo", "admin"];
+
+/// Desktop shells which register a polkit authentication agent on their own
+const AGENT_SHELLS: [&str; 4] = ["gnome-shell", "cinnamon", "budgie-daemon", "gala"];
+
+/// What the host offers to escalate privileges, reported by `Command::can_elevate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ElevationCapabilities {
+    /// Whether the current process already runs as root
+    pub elevated: bool,
+    /// The names of the backends available on the host, e.g. `pkexec` or `sudo`
+    pub backends: Vec<String>,
+    /// Whether a process of the user is known to be a polkit authentication agent,
+    /// which `pkexec` and `run0` need to prompt graphically
+    ///
+    /// It is a guess from the names of the running processes, so `false` does not mean
+    /// that there is none, as an agent unknown to this crate might be running
+    pub known_polkit_agent: bool,
+    /// Whether the user is a member of `wheel`, `sudo` or `admin`
+    pub privileged_group: bool,
+}
+
+impl ElevationCapabilities {
+    /// Probe the host
+    pub(crate) fn probe() -> Self {
+        let candidates: [&dyn Backend; 5] = [&Pkexec::new(), &Run0::new(), &Sudo::new(), &Doas::new(), &Su::new()];
+        Self {
+            elevated: Command::is_elevated(),
+            backends: candidates
+                .iter()
+                .filter(|backend| backend.is_available())
+                .map(|backend| backend.name().to_string())
+                .collect(),
+            known_polkit_agent: has_known_polkit_agent(),
+            privileged_group: group_names().iter().any(|name| PRIVILEGED_GROUPS.contains(&name.as_str())),
+        }
+    }
+
+    /// Whether the privileges could be escalated, i.e. the process already runs
+    /// as root or a backend is available
+    ///
+    /// It does not tell whether the user would be authorized
+    pub fn is_possible(&self) -> bool {
+        self.elevated || !self.backends.is_empty()
+    }
+}
+
+/// Look for a process of the current user known to be a polkit authentication agent
+fn has_known_polkit_agent() -> bool {
+    let uid = unsafe { libc::getuid() };
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.flatten().any(|entry| {
+        let dir = entry.path();
+        if !dir.metadata().is_ok_and(|metadata| metadata.uid() == uid) {
+            return false;
+        }
+        // the command name in `comm` is truncated, so take the one of `cmdline`
+        let cmdline = match fs::read(dir.join("cmdline")) {
+            Ok(cmdline) => cmdline,
+            Err(_) => return false,
+        };
+        let program = cmdline.split(|byte| *byte == 0).next().unwrap_or_default();
+        let name = Path::new(&*String::from_utf8_lossy(program))
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        (name.contains("polkit") && name != "polkitd")
+            || name.contains("policykit")
+            || AGENT_SHELLS.contains(&name.as_str())
+    })
+}
+
+/// The names of the groups the current process is a member of
+fn group_names() -> Vec<String> {
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count < 0 {
+        return vec![];
+    }
+    let mut gids = vec![0; count as usize];
+    let count = unsafe { libc::getgroups(count, gids.as_mut_ptr()) };
+    if count < 0 {
+        return vec![];
+    }
+    gids.truncate(count as usize);
+    gids.push(unsafe { libc::getgid() });
+
+    let mut names = vec![];
+    let mut buf = vec![0 as libc::c_char; 4096];
+    for gid in gids {
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        loop {
+            let err = unsafe { libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result) };
+            if err == libc::ERANGE {
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            break;
+        }
+        if !result.is_null() {
+            let name = unsafe { CStr::from_ptr(group.gr_name) };
+            names.push(name.to_string_lossy().into_owned());
+        }
+    }
+    names
+}
@@ -0,0 +1,374 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::backend;
+use crate::output::{self, OutputEvent, Stream};
+use crate::handoff::Handoff;
+use crate::{Backend, CancellationHandle, Error, Pty, Result};
+use std::io::{self, Read};
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, ExitStatus, Output};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the child is polled while waiting with a deadline or a cancellation handle
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the child is given to exit on `SIGTERM` before it is killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(1);
+
+/// Representation of a running or exited elevated child process
+///
+/// It is returned by `Command::spawn` and mirrors std::process::Child.
+/// The process being tracked is the backend, e.g. `pkexec`, which is
+/// replaced by or waits for the elevated program
+///
+/// The exit status is checked against the exit conventions of the backend,
+/// an `Error` is returned instead if it reports an elevation failure
+///
+/// As the elevated program runs as root, it cannot be signaled by an unprivileged parent,
+/// unless it runs behind the supervisor enabled by `Command::supervise`, which relays
+/// `ElevatedChild::signal` and tears the elevated program down when the current process
+/// exits. Dropping the handle leaves the elevated program running, until the current
+/// process exits if it is supervised
+///
+/// Waiting honors `Command::timeout` and `Command::cancellation`, which enable the
+/// supervisor: the backend and the elevated program are sent `SIGTERM`, then killed if
+/// they do not exit in time, and `Error::TimedOut` or `Error::Cancelled` is returned
+pub struct ElevatedChild {
+    /// The handle for writing to the child's standard input, if it has been captured
+    pub stdin: Option<ChildStdin>,
+    /// The handle for reading from the child's standard output, if it has been captured
+    pub stdout: Option<ChildStdout>,
+    /// The handle for reading from the child's standard error, if it has been captured
+    pub stderr: Option<ChildStderr>,
+    /// The handle to the pseudo-terminal of the child, if `Command::pty` is set
+    pub pty: Option<Pty>,
+    child: Child,
+    backend: Arc<dyn Backend>,
+    handoff: Handoff,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationHandle>,
+}
+
+impl ElevatedChild {
+    pub(crate) fn new(
+        mut child: Child,
+        backend: Arc<dyn Backend>,
+        mut handoff: Handoff,
+        deadline: Option<Instant>,
+        cancellation: Option<CancellationHandle>,
+    ) -> Self {
+        handoff.keep();
+        Self {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take(),
+            stderr: child.stderr.take(),
+            pty: None,
+            child,
+            backend,
+            handoff,
+            deadline,
+            cancellation,
+        }
+    }
+
+    /// Returns the OS-assigned process identifier of the backend process
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Waits for the child to exit completely, returning the status that it exited with
+    ///
+    /// The stdin handle to the child process, if any, will be closed before waiting
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     let mut child = elevated_cmd.spawn().unwrap();
+    ///     let status = child.wait().unwrap();
+    /// }
+    /// ```
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        drop(self.stdin.take());
+        let status = self.wait_limited()?;
+        self.close_files();
+        self.check(status)
+    }
+
+    /// Attempts to collect the exit status of the child if it has already exited
+    ///
+    /// Return `None` if the child is still running
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        match self.child.try_wait()? {
+            Some(status) => {
+                self.close_files();
+                self.check(status).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Send `signal`, e.g. `libc::SIGINT`, to the elevated program
+    ///
+    /// If it runs behind the supervisor, see `Command::supervise`, `SIGHUP`, `SIGINT`,
+    /// `SIGQUIT`, `SIGKILL`, `SIGUSR1`, `SIGUSR2`, `SIGTERM`, `SIGCONT`, `SIGSTOP` and
+    /// `SIGTSTP` could be sent to its process group, `Error::InvalidArgument` is returned
+    /// otherwise. The signal is lost if the user has not authenticated yet
+    ///
+    /// Otherwise, the signal is sent to the backend, which `sudo` relays to the elevated
+    /// program, while a backend which has switched to root, e.g. `pkexec`, cannot be
+    /// signaled by an unprivileged parent and an error of permission denied is returned
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Direct;
+    /// use std::process::Command as StdCommand;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("sh");
+    ///     cmd.args(["-c", "trap 'exit 7' USR1; sleep 10 & wait"]);
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new()).supervise(true);
+    ///     let mut child = elevated_cmd.spawn().unwrap();
+    ///     thread::sleep(Duration::from_millis(200));
+    ///     child.signal(10).unwrap();
+    ///     assert_eq!(child.wait().unwrap().code(), Some(7));
+    /// }
+    /// ```
+    pub fn signal(&mut self, signal: i32) -> Result<()> {
+        if let Some(ref mut control) = self.handoff.control {
+            return control.send(signal);
+        }
+        if self.child.try_wait()?.is_some() {
+            return Err(Error::InvalidArgument("the elevated program has exited".to_string()));
+        }
+        if unsafe { libc::kill(self.child.id() as libc::pid_t, signal) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// Forces the child process to exit
+    ///
+    /// The elevated program is killed through its supervisor, and the backend directly
+    /// while it is still unprivileged
+    pub fn kill(&mut self) -> Result<()> {
+        if let Some(ref mut control) = self.handoff.control {
+            control.send(libc::SIGKILL)?;
+        }
+        match self.child.kill() {
+            // the backend has switched to root, the supervisor kills the elevated program
+            Err(err) if err.raw_os_error() == Some(libc::EPERM) && self.handoff.control.is_some() => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Simultaneously waits for the child to exit and collect all remaining
+    /// output on the stdout/stderr handles, returning an `Output` instance
+    ///
+    /// The output on the pseudo-terminal, if any, is collected as stdout
+    pub fn wait_with_output(mut self) -> Result<Output> {
+        if self.deadline.is_none() && self.cancellation.is_none() && self.pty.is_none() {
+            self.child.stdin = self.stdin.take();
+            self.child.stdout = self.stdout.take();
+            self.child.stderr = self.stderr.take();
+            let output = self.child.wait_with_output()?;
+            self.handoff.close();
+            return check_output(self.backend.as_ref(), output);
+        }
+
+        drop(self.stdin.take());
+        let stdout = match self.pty.take() {
+            Some(pty) => Some(read_to_end(pty)),
+            None => self.stdout.take().map(read_to_end),
+        };
+        let stderr = self.stderr.take().map(read_to_end);
+        // on failure, the readers are left behind, as the pipes might be held open
+        // by an elevated program which could not be terminated
+        let status = self.wait_limited()?;
+        self.close_files();
+        let collect = |reader: Option<JoinHandle<io::Result<Vec<u8>>>>| match reader {
+            Some(reader) => reader.join().unwrap_or_else(|_| Ok(vec![])),
+            None => Ok(vec![]),
+        };
+        let output = Output {
+            status,
+            stdout: collect(stdout)?,
+            stderr: collect(stderr)?,
+        };
+        check_output(self.backend.as_ref(), output)
+    }
+
+    /// Waits for the child to exit like `ElevatedChild::wait_with_output`,
+    /// calling `on_line` on the current thread with each line of output as it arrives
+    pub(crate) fn wait_with_lines<F: FnMut(OutputEvent)>(mut self, mut on_line: F) -> Result<Output> {
+        drop(self.stdin.take());
+        let (sender, receiver) = mpsc::channel();
+        let stdout = match self.pty.take() {
+            Some(pty) => Some(output::read_lines(pty, Stream::Stdout, sender.clone())),
+            None => self.stdout.take().map(|stdout| output::read_lines(stdout, Stream::Stdout, sender.clone())),
+        };
+        let stderr = self.stderr.take().map(|stderr| output::read_lines(stderr, Stream::Stderr, sender.clone()));
+        drop(sender);
+
+        let (mut stdout_buf, mut stderr_buf) = (vec![], vec![]);
+        loop {
+            // on failure, the readers are left behind as in `ElevatedChild::wait_with_output`
+            self.check_limits()?;
+            let (stream, mut line, time) = match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            match stream {
+                Stream::Stdout => stdout_buf.extend(&line),
+                Stream::Stderr => stderr_buf.extend(&line),
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            on_line(OutputEvent { stream, line, time });
+        }
+        for reader in [stdout, stderr].into_iter().flatten() {
+            reader.join().unwrap_or(Ok(()))?;
+        }
+
+        let status = self.wait_limited()?;
+        self.close_files();
+        let output = Output {
+            status,
+            stdout: stdout_buf,
+            stderr: stderr_buf,
+        };
+        check_output(self.backend.as_ref(), output)
+    }
+
+    /// Wait for the child until the deadline or the cancellation, terminating it then
+    fn wait_limited(&mut self) -> Result<ExitStatus> {
+        if self.deadline.is_none() && self.cancellation.is_none() {
+            return Ok(self.child.wait()?);
+        }
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(status);
+            }
+            let mut interval = POLL_INTERVAL;
+            if let Some(deadline) = self.deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    self.terminate();
+                    return Err(Error::TimedOut);
+                }
+                interval = interval.min(deadline - now);
+            }
+            let cancelled = match self.cancellation {
+                Some(ref handle) => handle.wait_timeout(interval),
+                None => {
+                    thread::sleep(interval);
+                    false
+                }
+            };
+            if cancelled {
+                self.terminate();
+                return Err(Error::Cancelled);
+            }
+        }
+    }
+
+    /// Terminate the child if the deadline has passed or the run has been cancelled
+    fn check_limits(&mut self) -> Result<()> {
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            self.terminate();
+            return Err(Error::TimedOut);
+        }
+        if self.cancellation.as_ref().is_some_and(CancellationHandle::is_cancelled) {
+            self.terminate();
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Ask the backend and the elevated program to exit, kill them if they do not
+    /// in time, and reap the backend
+    fn terminate(&mut self) {
+        let pid = self.child.id() as libc::pid_t;
+        if let Some(ref mut control) = self.handoff.control {
+            let _ = control.send(libc::SIGTERM);
+        }
+        unsafe { libc::kill(pid, libc::SIGTERM) };
+        if self.exits_within(TERMINATE_GRACE) {
+            self.close_files();
+            return;
+        }
+        if let Some(ref mut control) = self.handoff.control {
+            let _ = control.send(libc::SIGKILL);
+        }
+        let _ = self.child.kill();
+        if self.exits_within(TERMINATE_GRACE) {
+            self.close_files();
+            return;
+        }
+        log::debug!("Cannot terminate the backend {}", pid);
+        thread::spawn(move || unsafe {
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        });
+    }
+
+    /// Whether the backend exits within `timeout`
+    fn exits_within(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Close the control pipe and remove the environment file once the backend has exited
+    fn close_files(&mut self) {
+        std::mem::take(&mut self.handoff).close();
+    }
+
+    /// Check the exit status of the backend, without its stderr which is not captured,
+    /// so that the failures it only reports on stderr, e.g. `Error::WouldPrompt`, are missed
+    fn check(&self, status: ExitStatus) -> Result<ExitStatus> {
+        match self.backend.failure(&status, &[]) {
+            Some(err) => Err(err),
+            None => Ok(status),
+        }
+    }
+}
+
+/// Check the output of the backend against its exit conventions
+fn check_output(backend: &dyn Backend, output: Output) -> Result<Output> {
+    match backend::output_failure(backend, &output) {
+        Some(err) => Err(err),
+        None => Ok(output),
+    }
+}
+
+/// Read `pipe` to the end in a separate thread
+fn read_to_end<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        pipe.read_to_end(&mut buf).map(|_| buf)
+    })
+}
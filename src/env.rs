@@ -0,0 +1,71 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::{Command, EnvPolicy};
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+
+/// The environment of the elevated program, computed from the `EnvPolicy`
+/// and the environment variables set on the wrapped std::process::Command
+pub(crate) struct Environment {
+    /// Whether the environment provided by the backend should be cleared first
+    pub clear: bool,
+    /// The variables to set, the explicit ones override the forwarded ones
+    pub vars: BTreeMap<OsString, OsString>,
+    /// The variables removed by std::process::Command::env_remove
+    pub removed: Vec<OsString>,
+}
+
+impl Environment {
+    /// Compute the environment of `cmd`, `session` lists the variables of the parent
+    /// forwarded by `EnvPolicy::Explicit` for the elevated program to reach the user session,
+    /// `forwarded` lists the ones forwarded whatever the policy is
+    pub fn new(cmd: &Command, session: &[&str], forwarded: &[OsString]) -> Self {
+        let mut vars = BTreeMap::new();
+        match cmd.env_policy {
+            EnvPolicy::Explicit => {
+                for key in session {
+                    if let Some(value) = env::var_os(key) {
+                        vars.insert(OsString::from(key), value);
+                    }
+                }
+            }
+            EnvPolicy::InheritAll => vars.extend(env::vars_os()),
+            EnvPolicy::Allowlist(ref keys) => {
+                for key in keys {
+                    if let Some(value) = env::var_os(key) {
+                        vars.insert(key.clone(), value);
+                    }
+                }
+            }
+            EnvPolicy::Clear => {}
+        }
+        for key in forwarded {
+            if let Some(value) = env::var_os(key) {
+                vars.insert(key.clone(), value);
+            }
+        }
+
+        let mut removed = vec![];
+        for (k, v) in cmd.cmd.get_envs() {
+            match v {
+                Some(value) => {
+                    vars.insert(k.to_os_string(), value.to_os_string());
+                }
+                None => {
+                    vars.remove(k);
+                    removed.push(k.to_os_string());
+                }
+            }
+        }
+
+        Self {
+            clear: matches!(cmd.env_policy, EnvPolicy::Clear),
+            vars,
+            removed,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// A specialized Result type for elevated command operations
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The error type for elevated command operations
+///
+/// The elevation failures are recognized from the exit conventions of each platform:
+///
+/// * `pkexec` exits with `126` when the user dismissed the authentication dialog,
+///   which is reported as `Cancelled`, and with `127` when the user is not authorized
+///   or the authentication failed, which is reported as `NotAuthorized`
+/// * `sudo`, `doas`, `run0` and `su` exit with `1` and report the failure on stderr,
+///   which is reported as `Cancelled` or `NotAuthorized` when stderr has been captured
+/// * In non-interactive mode, `sudo -n`, `doas -n` and `run0 --no-ask-password` report
+///   that a password is required on stderr, which is reported as `WouldPrompt` when stderr
+///   has been captured, as are `sudo` reporting that a terminal is required when it has
+///   no way to prompt the user, and `pkexec` reporting that no authentication agent is found
+/// * On Windows, `ShellExecuteW` returns `SE_ERR_ACCESSDENIED` when the user declined
+///   the UAC prompt, which is reported as `Cancelled`
+/// * On MacOS, the applet fails with the AppleScript error `-128` when the user
+///   cancelled the dialog, which is reported as `Cancelled`
+///
+/// As the exit code is shared with the elevated program, a program exiting with
+/// `126` or `127` on its own would be reported the same way when run through `pkexec`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The user dismissed the authentication dialog, or the run has been cancelled
+    /// through a `CancellationHandle`
+    Cancelled,
+    /// The run took longer than the timeout set by `Command::timeout`
+    TimedOut,
+    /// The user is not authorized to escalate privileges, or the authentication failed
+    NotAuthorized,
+    /// The backend would have to prompt the user, while the command is non-interactive
+    /// or the backend has no way to prompt, e.g. `sudo` without a terminal nor an askpass helper.
+    /// It is only recognized from the captured stderr of the backend, see `Command::non_interactive`
+    WouldPrompt,
+    /// No backend to escalate privileges could be found on the host
+    BackendNotFound,
+    /// The target user set by `Command::user` does not exist
+    UserNotFound(String),
+    /// The target group set by `Command::group` does not exist
+    GroupNotFound(String),
+    /// The backend cannot run the command as the given target user or group
+    CannotSwitchUser(String),
+    /// The command could not be passed to the backend as configured
+    InvalidArgument(String),
+    /// The working directory does not exist or is not accessible as root
+    WorkingDirectory(PathBuf),
+    /// The program could not be found in `PATH`
+    ProgramNotFound(OsString),
+    /// The backend failed for a reason other than authorization
+    Backend(String),
+    /// An I/O error occurred while launching or communicating with the backend
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Cancelled => write!(f, "the elevation was cancelled"),
+            Error::TimedOut => write!(f, "the elevated command timed out"),
+            Error::NotAuthorized => write!(f, "not authorized to escalate privileges"),
+            Error::WouldPrompt => write!(f, "authentication would require prompting the user"),
+            Error::BackendNotFound => write!(f, "no elevation backend found"),
+            Error::UserNotFound(user) => write!(f, "user not found: {}", user),
+            Error::GroupNotFound(group) => write!(f, "group not found: {}", group),
+            Error::CannotSwitchUser(target) => write!(f, "backend cannot run the command as {}", target),
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::WorkingDirectory(dir) => write!(f, "cannot change directory to {}", dir.display()),
+            Error::ProgramNotFound(program) => write!(f, "program not found: {}", program.to_string_lossy()),
+            Error::Backend(message) => write!(f, "backend failed: {}", message),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
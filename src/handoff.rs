@@ -0,0 +1,119 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::askpass::private_dir;
+use crate::supervisor::Control;
+use crate::Result;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The files handed off to the elevated side of a run, in the directory private to
+/// the current user: the control pipe of the supervisor and the environment file
+///
+/// It is passed to `Backend::command`, which adds the environment file if it needs one,
+/// so it has to be kept until the command has exited. Dropping it removes the files
+#[derive(Default)]
+pub struct Handoff {
+    pub(crate) control: Option<Control>,
+    pub(crate) env_file: Option<EnvFile>,
+}
+
+impl Handoff {
+    /// Constructs a new `Handoff`, without control pipe, for a command which is not supervised
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the files when the handoff is dropped, as the elevated side might not have
+    /// opened them yet, and the elevated program keeps running until the current process
+    /// exits if it is supervised
+    pub(crate) fn keep(&mut self) {
+        if let Some(ref mut control) = self.control {
+            control.keep_open();
+        }
+        if let Some(ref mut env_file) = self.env_file {
+            env_file.keep = true;
+        }
+    }
+
+    /// Remove the files once the elevated program has exited
+    pub(crate) fn close(self) {
+        if let Some(control) = self.control {
+            control.close();
+        }
+        if let Some(mut env_file) = self.env_file {
+            env_file.keep = false;
+        }
+    }
+}
+
+/// A file holding the variables to set as shell assignments, which is sourced by a shell
+/// in front of the elevated program, so that their values are not exposed on the command
+/// lines of the processes, which any user could read
+pub(crate) struct EnvFile {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl EnvFile {
+    /// Write `vars`, whose keys are shell names, into a new file readable by the current user only
+    pub fn create(vars: &[(OsString, OsString)]) -> Result<Self> {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let count = FILES.fetch_add(1, Ordering::Relaxed);
+        let path = private_dir()?.join(format!("env-{}-{}", std::process::id(), count));
+        let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path)?;
+        let env_file = Self { path, keep: false };
+        let mut script = vec![];
+        for (k, v) in vars {
+            script.extend(b"export ");
+            script.extend(k.as_bytes());
+            script.push(b'=');
+            script.extend(quote(v));
+            script.push(b'\n');
+        }
+        file.write_all(&script)?;
+        Ok(env_file)
+    }
+
+    /// The path to the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for EnvFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Whether `key` could be assigned by the shell, i.e. is made of ASCII letters,
+/// digits and underscores and does not start with a digit
+pub(crate) fn is_shell_name(key: &OsStr) -> bool {
+    let key = key.as_bytes();
+    !key.is_empty()
+        && !key[0].is_ascii_digit()
+        && key.iter().all(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+}
+
+/// Quote `value` for the shell within single quotes, but for the quotes themselves
+fn quote(value: &OsStr) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for byte in value.as_bytes() {
+        match byte {
+            b'\'' => quoted.extend(b"'\\''"),
+            byte => quoted.push(*byte),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
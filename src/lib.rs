@@ -7,7 +7,95 @@
 //! 
 //! elevated-command - Run command using `sudo`, prompting the user with a graphical OS dialog if necessary
 use std::convert::From;
+use std::ffi::OsString;
 use std::process::Command as StdCommand;
+#[cfg(target_os = "linux")]
+use std::process::Stdio;
+#[cfg(target_os = "linux")]
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+pub use error::{Error, Result};
+pub use relaunch::elevate_self;
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+pub use asynchronous::AsyncElevatedChild;
+#[cfg(target_os = "linux")]
+pub use backend::Backend;
+#[cfg(target_os = "linux")]
+pub use cancel::CancellationHandle;
+#[cfg(target_os = "linux")]
+pub use capabilities::ElevationCapabilities;
+#[cfg(target_os = "linux")]
+pub use child::ElevatedChild;
+#[cfg(target_os = "linux")]
+pub use output::{OutputEvent, Stream};
+#[cfg(target_os = "linux")]
+pub use privilege::Privilege;
+#[cfg(target_os = "linux")]
+pub use pty::Pty;
+#[cfg(target_os = "linux")]
+pub use session::ElevatedSession;
+
+/// Which environment variables of the current process are passed to the elevated program
+/// 
+/// The variables explicitly set by std::process::Command::env are always passed, 
+/// and the ones removed by std::process::Command::env_remove are never passed
+/// and unset in the elevated program
+/// 
+/// The policy is only applicable on Linux and MacOS
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EnvPolicy {
+    /// Only pass the explicitly set variables, plus `DISPLAY`, `XAUTHORITY` and `HOME`
+    /// on Linux for the elevated program to reach the user session
+    #[default]
+    Explicit,
+    /// Pass all the variables of the current process
+    ///
+    /// On Linux, the values are kept off the command lines, which any user can read through
+    /// `/proc` and whose size is limited by `ARG_MAX`: they are written to a file readable by
+    /// the current user only, in its private directory, which a shell run as root sources in
+    /// front of the elevated program, and `run0` takes them from its own environment.
+    /// The exceptions go on the command line of `env`: the variables whose names the shell
+    /// cannot assign, and all of them for a `Command::user` other than root unless the program
+    /// is supervised, see `Command::supervise`. As the shell is the program the backend runs,
+    /// the rules scoped to the elevated program do not apply, see the `backend` module.
+    /// Prefer `EnvPolicy::Allowlist` when the current process holds secrets
+    InheritAll,
+    /// Pass the variables of the current process with the given names
+    Allowlist(Vec<OsString>),
+    /// Only pass the explicitly set variables and clear the environment provided by the backend on Linux,
+    /// which is the counterpart of std::process::Command::env_clear as it cannot be read back
+    Clear,
+}
+
+/// How the user is prompted for authorization
+/// 
+/// The mode is only applicable on Linux
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptMode {
+    /// Prompt with a graphical dialog
+    Gui,
+    /// Prompt on the controlling terminal, for SSH sessions and CI runners
+    Tty,
+    /// Prompt with a graphical dialog if the current process runs in a graphical 
+    /// session, i.e. `DISPLAY` or `WAYLAND_DISPLAY` is set, or else on the terminal
+    #[default]
+    Auto,
+}
+
+#[cfg(target_os = "linux")]
+impl PromptMode {
+    /// Resolve `Auto` into `Gui` or `Tty` according to the current session
+    pub(crate) fn resolve(self) -> Self {
+        match self {
+            PromptMode::Auto if backend::is_graphical_session() => PromptMode::Gui,
+            PromptMode::Auto => PromptMode::Tty,
+            mode => mode,
+        }
+    }
+}
 
 /// Wrap of std::process::command and escalate privileges while executing
 pub struct Command {
@@ -16,6 +104,36 @@ pub struct Command {
     icon: Option<Vec<u8>>,
     #[allow(dead_code)]
     name: Option<String>,
+    #[allow(dead_code)]
+    env_policy: EnvPolicy,
+    #[cfg(target_os = "linux")]
+    backend: Option<Arc<dyn Backend>>,
+    #[cfg(target_os = "linux")]
+    prompt_mode: PromptMode,
+    #[cfg(target_os = "linux")]
+    non_interactive: bool,
+    #[cfg(target_os = "linux")]
+    user: Option<String>,
+    #[cfg(target_os = "linux")]
+    group: Option<String>,
+    #[cfg(target_os = "linux")]
+    timeout: Option<Duration>,
+    #[cfg(target_os = "linux")]
+    cancellation: Option<CancellationHandle>,
+    #[cfg(target_os = "linux")]
+    supervise: bool,
+    #[cfg(target_os = "linux")]
+    forward_gui_session: bool,
+    #[cfg(target_os = "linux")]
+    resolve_program: bool,
+    #[cfg(target_os = "linux")]
+    pty: bool,
+    #[cfg(target_os = "linux")]
+    stdin: Option<Stdio>,
+    #[cfg(target_os = "linux")]
+    stdout: Option<Stdio>,
+    #[cfg(target_os = "linux")]
+    stderr: Option<Stdio>,
 }
 
 /// Command initialization shares the same logic across all the platforms
@@ -27,22 +145,25 @@ impl Command {
     /// * The instance's path to the program
     /// * The instance's arguments
     /// * The instance's environment variables
+    /// * The instance's working directory
     /// 
-    /// So far, the new `Command` would only take the environment variables explicitly 
-    /// set by std::process::Command::env and std::process::Command::env, 
-    /// without the ones inherited from the parent process
+    /// By default, the new `Command` would only take the environment variables explicitly 
+    /// set by std::process::Command::env and std::process::Command::envs, 
+    /// without the ones inherited from the parent process, see `Command::env_policy`
     /// 
     /// And the environment variables would only be taken on Linux and MacOS, 
     /// they would be ignored on Windows
     /// 
-    /// Current working directory would be the following while executing the command:
+    /// The working directory set by std::process::Command::current_dir is honored 
+    /// on Linux and MacOS, a relative one is resolved against the working directory 
+    /// of the current process. `Error::WorkingDirectory` is returned if it does not 
+    /// exist or cannot be entered as root. Without it, the working directory would be 
+    /// the following while executing the command:
     ///   - %SystemRoot%\System32 on Windows
     ///   - /root on Linux
     ///   - $TMPDIR/sudo_prompt_applet/applet.app/Contents/MacOS on MacOS
     /// 
-    /// To pass environment variables on Windows, 
-    /// to inherit environment variables from the parent process and 
-    /// to change the working directory will be supported in later versions
+    /// To pass environment variables on Windows will be supported in later versions
     /// 
     /// # Examples
     ///
@@ -64,6 +185,36 @@ impl Command {
             cmd,
             icon: None,
             name: None,
+            env_policy: EnvPolicy::default(),
+            #[cfg(target_os = "linux")]
+            backend: None,
+            #[cfg(target_os = "linux")]
+            prompt_mode: PromptMode::default(),
+            #[cfg(target_os = "linux")]
+            non_interactive: false,
+            #[cfg(target_os = "linux")]
+            user: None,
+            #[cfg(target_os = "linux")]
+            group: None,
+            #[cfg(target_os = "linux")]
+            timeout: None,
+            #[cfg(target_os = "linux")]
+            cancellation: None,
+            #[cfg(target_os = "linux")]
+            supervise: false,
+            #[cfg(target_os = "linux")]
+            #[cfg(target_os = "linux")]
+            forward_gui_session: false,
+            #[cfg(target_os = "linux")]
+            resolve_program: true,
+            #[cfg(target_os = "linux")]
+            pty: false,
+            #[cfg(target_os = "linux")]
+            stdin: None,
+            #[cfg(target_os = "linux")]
+            stdout: None,
+            #[cfg(target_os = "linux")]
+            stderr: None,
         }
     }
 
@@ -113,7 +264,7 @@ impl Command {
     ///
     /// fn main() {
     ///     let mut cmd = StdCommand::new("path to the application");
-    ///     let elevated_cmd = Command::new(cmd);
+    ///     let mut elevated_cmd = Command::new(cmd);
     ///     let cmd = elevated_cmd.get_mut();
     /// }
     /// ```
@@ -133,8 +284,8 @@ impl Command {
     ///
     /// fn main() {
     ///     let mut cmd = StdCommand::new("path to the application");
-    ///     let elevated_cmd = Command::new(cmd);
-    ///     elevated_cmd.icon(include_bytes!("path to the icon").to_vec());
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.icon(std::fs::read("path to the icon").unwrap());
     /// }
     /// ```
     pub fn icon(&mut self, icon: Vec<u8>) -> &mut Self {
@@ -154,7 +305,7 @@ impl Command {
     ///
     /// fn main() {
     ///     let mut cmd = StdCommand::new("path to the application");
-    ///     let elevated_cmd = Command::new(cmd);
+    ///     let mut elevated_cmd = Command::new(cmd);
     ///     elevated_cmd.name("some name".to_string());
     /// }
     /// ```
@@ -162,6 +313,439 @@ impl Command {
         self.name = Some(name);
         self
     }
+
+    /// Set which environment variables of the current process are passed to the elevated program
+    /// 
+    /// This method is only applicable on `Linux` and `MacOS`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::{Command, EnvPolicy};
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.env_policy(EnvPolicy::Allowlist(vec!["LANG".into(), "TERM".into()]));
+    /// }
+    /// ```
+    pub fn env_policy(&mut self, policy: EnvPolicy) -> &mut Self {
+        self.env_policy = policy;
+        self
+    }
+
+    /// Set the backend used to escalate privileges, instead of
+    /// probing the host for an available one
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Sudo;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Sudo::new());
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn backend<B: Backend + 'static>(&mut self, backend: B) -> &mut Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Set how the user is prompted for authorization, which decides the backend
+    /// picked when none is set by `Command::backend`, see the `backend` module
+    /// 
+    /// By default, it prompts with a graphical dialog in a graphical session, 
+    /// or else on the controlling terminal through `pkexec`'s textual agent or `sudo`
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::{Command, PromptMode};
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.prompt_mode(PromptMode::Tty);
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn prompt_mode(&mut self, mode: PromptMode) -> &mut Self {
+        self.prompt_mode = mode;
+        self
+    }
+
+    /// Never prompt the user, the command only succeeds if the credentials are cached 
+    /// or a rule grants the privileges without authentication, e.g. `NOPASSWD` of sudo
+    /// 
+    /// The backends run in their no-prompt mode: `sudo -n`, `doas -n` and
+    /// `run0 --no-ask-password`. `Error::WouldPrompt` is returned instead of prompting.
+    /// `pkexec`, which prompts through any registered authentication agent, and `su`
+    /// have no such mode, so they are never picked, and fail with `Error::WouldPrompt`
+    /// when set by `Command::backend`
+    /// 
+    /// The backends report that they would prompt on stderr, so `Error::WouldPrompt` is only
+    /// returned when it is captured: by `output`, `output_with`, and `ElevatedChild::wait_with_output`
+    /// with `Command::stderr` piped. Otherwise, e.g. with `status` or `ElevatedChild::wait`,
+    /// the failure is the exit status `1` of the backend, its message being written to stderr
+    /// 
+    /// A `NOPASSWD` rule scoped to the program only applies when the backend runs
+    /// the program itself, see the `backend` module
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::{Command, Error};
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.non_interactive(true);
+    ///     match elevated_cmd.output() {
+    ///         Err(Error::WouldPrompt) => println!("run it from the application instead"),
+    ///         output => println!("{:?}", output),
+    ///     }
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn non_interactive(&mut self, non_interactive: bool) -> &mut Self {
+        self.non_interactive = non_interactive;
+        self
+    }
+
+    /// Run the command as the given user instead of root
+    /// 
+    /// It is passed as `pkexec --user`, `sudo -u`, `doas -u`, `run0 --user`
+    /// and to `su`. `Error::UserNotFound` is returned if the user does not exist
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("psql");
+    ///     cmd.arg("--list");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.user("postgres");
+    ///     let output = elevated_cmd.output().unwrap();
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn user<S: Into<String>>(&mut self, user: S) -> &mut Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Run the command with the given primary group, the one of
+    /// the target user is used otherwise
+    /// 
+    /// It is passed as `sudo -g` and `run0 --group`, the other backends cannot
+    /// switch the group, for which `Error::CannotSwitchUser` is returned.
+    /// `Error::GroupNotFound` is returned if the group does not exist
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Backend, Command};
+    /// use elevated_command::backend::{Handoff, Sudo};
+    /// use std::ffi::OsStr;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut elevated_cmd = Command::new(StdCommand::new("/bin/true"));
+    ///     elevated_cmd.user("root").group("root");
+    ///     let command = Sudo::new().program("/bin/sh").command(&elevated_cmd, &mut Handoff::new()).unwrap();
+    ///     let args: Vec<&OsStr> = command.get_args().collect();
+    ///     assert_eq!(&args[..4], ["-u", "root", "-g", "root"]);
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn group<S: Into<String>>(&mut self, group: S) -> &mut Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Limit how long the run may take, from the prompt to the exit of the elevated program
+    /// 
+    /// Once it elapses, the backend and the elevated program are terminated
+    /// and `Error::TimedOut` is returned, see `ElevatedChild`. It enables
+    /// the supervisor, see `Command::supervise`
+    /// 
+    /// This method is only applicable on `Linux`, the asynchronous methods
+    /// ignore it, as the future can be dropped instead
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Command, Error};
+    /// use elevated_command::backend::Direct;
+    /// use std::process::Command as StdCommand;
+    /// use std::time::Duration;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("sleep");
+    ///     cmd.arg("10");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new()).timeout(Duration::from_millis(100));
+    ///     assert!(matches!(elevated_cmd.output(), Err(Error::TimedOut)));
+    ///     assert!(matches!(elevated_cmd.status(), Err(Error::TimedOut)));
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set the handle cancelling the run from another thread, the backend and
+    /// the elevated program are then terminated and `Error::Cancelled` is returned,
+    /// see `CancellationHandle`. It enables the supervisor, see `Command::supervise`
+    /// 
+    /// This method is only applicable on `Linux`
+    #[cfg(target_os = "linux")]
+    pub fn cancellation(&mut self, handle: CancellationHandle) -> &mut Self {
+        self.cancellation = Some(handle);
+        self
+    }
+
+    /// Run the elevated program behind a supervisor, so that it could be signaled
+    /// and torn down by the unprivileged current process, it is disabled by default
+    /// 
+    /// The supervisor is a shell running as root, which starts the elevated program in
+    /// its own process group, in the foreground of the terminal its stdin is attached to
+    /// if any. It relays to the group the signals sent by `ElevatedChild::signal`, as well
+    /// as `SIGINT`, `SIGTERM` and `SIGHUP` it receives, and sends it `SIGTERM`, then
+    /// `SIGKILL`, when the current process exits or dies. It is controlled through a named
+    /// pipe rather than `PR_SET_PDEATHSIG`, which the kernel clears when a setuid backend
+    /// is executed. It requires `setsid`, and `runuser` to switch to a target user other
+    /// than root set by `Command::user`, as the backend then runs the supervisor as root
+    /// 
+    /// The backend is handed the supervisor instead of the program, so that the rules
+    /// scoped to the program, e.g. a `NOPASSWD: /usr/bin/apt` rule of sudoers, a `cmd`
+    /// rule of doas or a polkit action with an `exec.path`, do not apply to it, and
+    /// `pkexec` asks the user to authorize `/bin/sh`
+    /// 
+    /// It is enabled by `Command::timeout` and `Command::cancellation` as well
+    /// 
+    /// This method is only applicable on `Linux`
+    #[cfg(target_os = "linux")]
+    pub fn supervise(&mut self, supervise: bool) -> &mut Self {
+        self.supervise = supervise;
+        self
+    }
+
+    /// Forward the variables of the graphical session to the elevated program, 
+    /// so that GUI programs could open windows on Wayland and reach the session bus
+    /// 
+    /// The forwarded variables are `DISPLAY`, `XAUTHORITY`, `WAYLAND_DISPLAY`, 
+    /// `XDG_RUNTIME_DIR`, `XDG_SESSION_TYPE`, `XDG_CURRENT_DESKTOP`, 
+    /// `DBUS_SESSION_BUS_ADDRESS` and the locale ones `LANG`, `LANGUAGE` and `LC_*`, 
+    /// whatever the `EnvPolicy` is
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Backend, Command};
+    /// use elevated_command::backend::{Handoff, Pkexec};
+    /// use std::env;
+    /// use std::ffi::OsStr;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     env::set_var("WAYLAND_DISPLAY", "wayland-0");
+    ///     env::set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+    ///     env::set_var("LC_TIME", "en_GB.UTF-8");
+    ///
+    ///     let mut cmd = StdCommand::new("/usr/bin/gparted");
+    ///     cmd.arg("/dev/sda");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.forward_gui_session(true);
+    ///
+    ///     let pkexec = Pkexec::new().program("/bin/sh");
+    ///     let command = pkexec.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+    ///     let args: Vec<&OsStr> = command.get_args().collect();
+    ///     let env = args.iter().position(|arg| *arg == "env").unwrap();
+    ///     let program = args.iter().position(|arg| *arg == "/usr/bin/gparted").unwrap();
+    ///     assert!(env < program);
+    ///     assert!(args[env..program].contains(&OsStr::new("WAYLAND_DISPLAY=wayland-0")));
+    ///     assert!(args[env..program].contains(&OsStr::new("XDG_RUNTIME_DIR=/run/user/1000")));
+    ///     assert!(args[env..program].contains(&OsStr::new("LC_TIME=en_GB.UTF-8")));
+    ///     assert_eq!(args[program + 1], "/dev/sda");
+    ///
+    ///     elevated_cmd.forward_gui_session(false);
+    ///     let command = pkexec.command(&elevated_cmd, &mut Handoff::new()).unwrap();
+    ///     assert!(!command.get_args().any(|arg| arg == "WAYLAND_DISPLAY=wayland-0"));
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn forward_gui_session(&mut self, forward: bool) -> &mut Self {
+        self.forward_gui_session = forward;
+        self
+    }
+
+    /// Resolve the program into an absolute path before handing it to the backend, 
+    /// it is enabled by default
+    /// 
+    /// A program name without `/` is searched through the `PATH` set on the wrapped 
+    /// std::process::Command, or else the one of the current process, so that it is 
+    /// the same program as the one std::process::Command would run. 
+    /// `Error::ProgramNotFound` is returned if it cannot be found. When disabled, 
+    /// the program is passed as is and searched by the backend through the `PATH` of root
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("fdisk");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.resolve_program(false);
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn resolve_program(&mut self, resolve: bool) -> &mut Self {
+        self.resolve_program = resolve;
+        self
+    }
+
+    /// Attach the elevated program to a pseudo-terminal, for the programs which behave
+    /// differently when their output is not a terminal or prompt the user on it
+    /// 
+    /// With `spawn`, the stdio configured through `Command::stdin`, `Command::stdout` and
+    /// `Command::stderr` is ignored, and the terminal is handed over as `ElevatedChild::pty`,
+    /// which has to be read from for the program not to block on writing to it. `output`
+    /// collects the terminal output as stdout, and ends the input of the program with
+    /// end of file, i.e. Ctrl-D, for it not to wait for input forever. `status` copies
+    /// the terminal output to the stdout of the current process, and forwards its stdin
+    /// to the terminal, switching the terminal of the current process to raw mode while
+    /// the program runs, so that the keys typed, e.g. Ctrl-C, reach the program, and
+    /// resizing the pseudo-terminal along with it through a handler of `SIGWINCH`
+    /// 
+    /// The backends authenticating through stdio, e.g. `Sudo::password`, are not
+    /// supported, for which `Error::InvalidArgument` is returned
+    /// 
+    /// This method is only applicable on `Linux`, see `Pty`
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::Command;
+    /// use elevated_command::backend::Direct;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("sh");
+    ///     cmd.args(["-c", "cat; [ -t 0 ] && echo done"]);
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new()).pty(true);
+    ///     let output = elevated_cmd.output().unwrap();
+    ///     assert_eq!(output.stdout, b"done\r\n");
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn pty(&mut self, pty: bool) -> &mut Self {
+        self.pty = pty;
+        self
+    }
+
+    /// Configuration for the elevated child process's standard input (stdin) handle
+    /// 
+    /// The stdio configuration of the wrapped std::process::Command cannot be read back,
+    /// so it has to be set on the `Command` instead. Defaults to inherit when used with
+    /// `spawn` and is ignored by `output`
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::{Command as StdCommand, Stdio};
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.stdin(Stdio::piped());
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.stdin = Some(cfg.into());
+        self
+    }
+
+    /// Configuration for the elevated child process's standard output (stdout) handle
+    /// 
+    /// Defaults to inherit when used with `spawn` and is ignored by `output`
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::{Command as StdCommand, Stdio};
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.stdout(Stdio::piped());
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.stdout = Some(cfg.into());
+        self
+    }
+
+    /// Configuration for the elevated child process's standard error (stderr) handle
+    /// 
+    /// Defaults to inherit when used with `spawn` and is ignored by `output`
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::{Command as StdCommand, Stdio};
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.stderr(Stdio::piped());
+    /// }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.stderr = Some(cfg.into());
+        self
+    }
 }
 
 impl From<StdCommand> for Command {
@@ -169,17 +753,43 @@ impl From<StdCommand> for Command {
     /// 
     /// It is similiar with the construct method
     fn from(cmd: StdCommand) -> Self {
-        Self {
-            cmd,
-            icon: None,
-            name: None,
-        }
+        Self::new(cmd)
     }
 }
 
+#[cfg(target_os = "linux")]
+mod askpass;
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+mod asynchronous;
+#[cfg(target_family = "unix")]
+mod env;
+mod error;
+mod relaunch;
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "linux")]
 mod linux;
+#[cfg(target_os = "linux")]
+pub mod backend;
+#[cfg(target_os = "linux")]
+mod cancel;
+#[cfg(target_os = "linux")]
+mod capabilities;
+#[cfg(target_os = "linux")]
+mod child;
+#[cfg(target_os = "linux")]
+mod handoff;
+#[cfg(target_os = "linux")]
+mod output;
+#[cfg(target_os = "linux")]
+mod password;
+#[cfg(target_os = "linux")]
+mod privilege;
+#[cfg(target_os = "linux")]
+mod pty;
+#[cfg(target_os = "linux")]
+mod session;
+#[cfg(target_os = "linux")]
+mod supervisor;
 #[cfg(target_os = "macos")]
 mod macos;
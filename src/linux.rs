@@ -3,19 +3,24 @@
  *  Licensed under the MIT License. See License in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
-use crate::Command;
-use anyhow::{anyhow, Result};
-use std::env;
-use std::ffi::OsStr;
-use std::path::PathBuf;
-use std::process::{Command as StdCommand, Output};
-use std::str::FromStr;
+use crate::backend;
+use crate::handoff::Handoff;
+use crate::pty::{self, RawMode, ResizeRelay};
+use crate::supervisor::Control;
+use crate::{Backend, CancellationHandle, Command, ElevatedChild, ElevationCapabilities, Error, OutputEvent, Privilege, Pty, Result};
+use std::io::Write;
+use std::process::{Command as StdCommand, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
 /// The implementation of state check and elevated executing varies on each platform
 impl Command {
     /// Check the state the current program running
     /// 
-    /// Return `true` if the program is running as root, otherwise false
+    /// Return `true` if the program is running as root, be it through a setuid
+    /// program, or holds `CAP_SYS_ADMIN`, otherwise false. Being root inside a user
+    /// namespace only, e.g. in a rootless container, does not count
     /// 
     /// # Examples
     ///
@@ -28,14 +33,59 @@ impl Command {
     /// }
     /// ```
     pub fn is_elevated() -> bool {
-        let uid = unsafe { 
-            libc::getuid()
-        };
-        if uid == 0 { 
-            true 
-        } else { 
-            false 
-        }
+        Self::privilege().is_elevated()
+    }
+
+    /// Report the privileges the current program holds: the real and effective
+    /// user ids, the effective capabilities, and whether it runs in a user namespace
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Command, Privilege};
+    ///
+    /// fn main() {
+    ///     let privilege = Command::privilege();
+    ///     if privilege.is_namespaced_root() {
+    ///         println!("root inside a user namespace only");
+    ///     }
+    ///     if privilege.has_capability(Privilege::CAP_SYS_ADMIN) {
+    ///         println!("holding CAP_SYS_ADMIN");
+    ///     }
+    ///     assert_eq!(Command::is_elevated(), privilege.is_elevated());
+    /// }
+    /// ```
+    pub fn privilege() -> Privilege {
+        Privilege::current()
+    }
+
+    /// Probe what the host offers to escalate privileges, e.g. to decide
+    /// whether to offer an action requiring them
+    /// 
+    /// Return the available backends, whether a polkit agent is known to run
+    /// and whether the user is in a privileged group, along with `Command::is_elevated`
+    /// 
+    /// It does not run any backend. Whether `sudo` would run a command without prompting
+    /// is checked by `Sudo::probe` instead, which runs `sudo -n true` that sudo might log
+    /// as an authentication failure, or report by mail
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::Command;
+    ///
+    /// fn main() {
+    ///     let capabilities = Command::can_elevate();
+    ///     println!("{:?}", capabilities);
+    ///     if capabilities.is_possible() {
+    ///         println!("Install system-wide");
+    ///     }
+    /// }
+    /// ```
+    pub fn can_elevate() -> ElevationCapabilities {
+        ElevationCapabilities::probe()
     }
 
     /// Prompting the user with a graphical OS dialog for the root password, 
@@ -54,45 +104,213 @@ impl Command {
     /// }
     /// ```
     pub fn output(&self) -> Result<Output> {
-        let pkexec = PathBuf::from_str("/bin/pkexec")?;
-        let mut command = StdCommand::new(pkexec);
-        let display = env::var("DISPLAY");
-        let xauthority = env::var("XAUTHORITY");
-        let home = env::var("HOME");
+        let mut child = self.launch(Stdio::null(), Stdio::piped(), Stdio::piped())?;
+        // the stdin might have been piped for the authentication
+        drop(child.stdin.take());
+        end_pty_input(&mut child);
+        child.wait_with_output()
+    }
 
-        command.arg("--disable-internal-agent");
-        if display.is_ok() || xauthority.is_ok() || home.is_ok() {
-            command.arg("env");
-            if let Ok(display) = display {
-                command.arg(format!("DISPLAY={}", display));
-            }
-            if let Ok(xauthority) = xauthority {
-                command.arg(format!("XAUTHORITY={}", xauthority));
-            }
-            if let Ok(home) = home {
-                command.arg(format!("HOME={}", home));
-            }
-        } else {
-            if self.cmd.get_envs().any(|(_, v)| v.is_some()) {
-                command.arg("env");
-            }
+    /// Prompting the user with a graphical OS dialog for the root password,
+    /// executing the command with escalated privileges, calling `on_line` with
+    /// each line of its output as it arrives, and return the whole output
+    /// 
+    /// `on_line` is called on the current thread, with the lines of stdout and stderr
+    /// in the order they are read, each tagged with its stream and the time it is read.
+    /// A line is passed once its newline is written, or the stream is closed
+    /// 
+    /// This method is only applicable on `Linux`
+    /// 
+    /// # Examples
+    ///
+    /// ```
+    /// use elevated_command::{Command, Stream};
+    /// use elevated_command::backend::Direct;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("sh");
+    ///     cmd.args(["-c", "echo 50%; echo warning >&2; printf 100%%"]);
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.backend(Direct::new());
+    ///
+    ///     let mut progress = vec![];
+    ///     let output = elevated_cmd
+    ///         .output_with(|event| match event.stream {
+    ///             Stream::Stdout => progress.push(event.text().into_owned()),
+    ///             Stream::Stderr => assert_eq!(event.line, b"warning"),
+    ///         })
+    ///         .unwrap();
+    ///     assert_eq!(progress, ["50%", "100%"]);
+    ///     assert_eq!(output.stdout, b"50%\n100%");
+    ///     assert_eq!(output.stderr, b"warning\n");
+    ///     assert!(output.status.success());
+    /// }
+    /// ```
+    pub fn output_with<F: FnMut(OutputEvent)>(&self, on_line: F) -> Result<Output> {
+        let mut child = self.launch(Stdio::null(), Stdio::piped(), Stdio::piped())?;
+        // the stdin might have been piped for the authentication
+        drop(child.stdin.take());
+        end_pty_input(&mut child);
+        child.wait_with_lines(on_line)
+    }
+
+    /// Prompting the user with a graphical OS dialog for the root password,
+    /// executing the command with escalated privileges as a child process,
+    /// and return a handle to it
+    /// 
+    /// The stdio handles are configured through `Command::stdin`, `Command::stdout`
+    /// and `Command::stderr`, they are inherited from the parent by default
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::io::{BufRead, BufReader};
+    /// use std::process::{Command as StdCommand, Stdio};
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     elevated_cmd.stdout(Stdio::piped());
+    ///     let mut child = elevated_cmd.spawn().unwrap();
+    ///     let stdout = child.stdout.take().unwrap();
+    ///     for line in BufReader::new(stdout).lines() {
+    ///         println!("{}", line.unwrap());
+    ///     }
+    ///     let status = child.wait().unwrap();
+    /// }
+    /// ```
+    pub fn spawn(&mut self) -> Result<ElevatedChild> {
+        let stdin = self.stdin.take().unwrap_or_else(Stdio::inherit);
+        let stdout = self.stdout.take().unwrap_or_else(Stdio::inherit);
+        let stderr = self.stderr.take().unwrap_or_else(Stdio::inherit);
+        self.launch(stdin, stdout, stderr)
+    }
+
+    /// Prompting the user with a graphical OS dialog for the root password,
+    /// executing the command with escalated privileges, waiting for it to
+    /// finish and return its exit status
+    /// 
+    /// Unlike `output`, the stdio handles are inherited from the parent by default,
+    /// so the elevated program reads from and writes to the terminal directly
+    /// 
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use elevated_command::Command;
+    /// use std::process::Command as StdCommand;
+    ///
+    /// fn main() {
+    ///     let mut cmd = StdCommand::new("path to the application");
+    ///     let mut elevated_cmd = Command::new(cmd);
+    ///     let status = elevated_cmd.status().unwrap();
+    /// }
+    /// ```
+    pub fn status(&mut self) -> Result<ExitStatus> {
+        let mut child = self.spawn()?;
+        let pty = match child.pty.take() {
+            Some(pty) => pty,
+            None => return child.wait(),
+        };
+        let input = pty.try_clone()?;
+        let raw_mode = RawMode::enable();
+        let resize_relay = ResizeRelay::install();
+        let done = Arc::new(AtomicBool::new(false));
+        let forwarding = pty::forward_input(input, done.clone());
+        let output = pty::forward_output(pty);
+        let status = child.wait();
+        done.store(true, Ordering::Relaxed);
+        let _ = forwarding.join();
+        // the output still buffered in the pseudo-terminal is written out before
+        // the terminal of the current process leaves raw mode
+        let _ = output.join();
+        drop(resize_relay);
+        drop(raw_mode);
+        status
+    }
+
+    /// The backend set by `Command::backend`, or the first one available on the host
+    fn resolve_backend(&self) -> Result<Arc<dyn Backend>> {
+        match self.backend {
+            Some(ref backend) => Ok(backend.clone()),
+            None => backend::detect(self).map(Arc::from).ok_or(Error::BackendNotFound),
+        }
+    }
+
+    /// Spawn the backend with the given stdio and authenticate with it
+    pub(crate) fn launch(&self, stdin: Stdio, stdout: Stdio, stderr: Stdio) -> Result<ElevatedChild> {
+        if self.cancellation.as_ref().is_some_and(CancellationHandle::is_cancelled) {
+            return Err(Error::Cancelled);
         }
-        for (k, v) in self.cmd.get_envs() {
-            if let Some(value) = v {
-                command.arg(format!("{}={}",
-                    k.to_str().ok_or(anyhow!("invalid key"))?,
-                    value.to_str().ok_or(anyhow!("invalid value"))?
-                ));
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let (pty, stdin, stdout, stderr) = match self.pty {
+            true => {
+                let (pty, slave) = Pty::open()?;
+                let (stdin, stdout) = (slave.try_clone()?, slave.try_clone()?);
+                (Some(pty), stdin.into(), stdout.into(), slave.into())
             }
+            false => (None, stdin, stdout, stderr),
+        };
+        let (backend, mut command, handoff) = self.prepare(stdin, stdout, stderr)?;
+        let mut child = command.spawn()?;
+        // the slave side of the pseudo-terminal is only held open by the backend
+        drop(command);
+        backend.authenticate(&mut child, deadline, self.cancellation.as_ref())?;
+        let mut child = ElevatedChild::new(child, backend, handoff, deadline, self.cancellation.clone());
+        child.pty = pty;
+        Ok(child)
+    }
+
+    /// Build the command running the backend with the given stdio, along with the files
+    /// handed off to the elevated side: the control pipe of the supervisor of the elevated
+    /// program, if it is supervised, and the file passing its environment, if any
+    ///
+    /// The files are removed if they are dropped before the backend has been launched
+    pub(crate) fn prepare(
+        &self,
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> Result<(Arc<dyn Backend>, StdCommand, Handoff)> {
+        let backend = self.resolve_backend()?;
+        if self.pty && backend.authenticates_through_stdio() {
+            return Err(Error::InvalidArgument(format!(
+                "{} authenticates through stdio, which is not supported with a pseudo-terminal",
+                backend.name()
+            )));
+        }
+        if let (Some(ref group), false) = (&self.group, backend.supports_group()) {
+            return Err(Error::CannotSwitchUser(format!("group {}", group)));
         }
+        // the run cannot be torn down by an unprivileged parent without the supervisor
+        let control = match self.supervise || self.timeout.is_some() || self.cancellation.is_some() {
+            true => Some(Control::new()?),
+            false => None,
+        };
+        let mut handoff = Handoff { control, env_file: None };
+        let mut command = backend.command(self, &mut handoff)?;
+        log::debug!("Elevated command: {:?}", command);
 
-        command.arg(self.cmd.get_program());
-        let args: Vec<&OsStr> = self.cmd.get_args().collect();
-        if !args.is_empty() {
-            command.args(args);
+        command.stdout(stdout);
+        if self.pty {
+            Pty::attach(&mut command);
+        }
+        if backend.authenticates_through_stdio() {
+            command.stdin(Stdio::piped());
+            command.stderr(Stdio::piped());
+        } else {
+            command.stdin(stdin);
+            command.stderr(stderr);
         }
+        Ok((backend, command, handoff))
+    }
+}
 
-        let output = command.output()?;
-        Ok(output)
+/// End the input of the program attached to a pseudo-terminal, which nothing writes to,
+/// so that it does not wait for input forever
+fn end_pty_input(child: &mut ElevatedChild) {
+    if let Some(ref mut pty) = child.pty {
+        let _ = pty.write_all(&[pty::END_OF_FILE]);
     }
 }
@@ -0,0 +1,63 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+/// The stream a line of output has been written to
+///
+/// This enum is only available on `Linux`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// The standard output, or the pseudo-terminal if `Command::pty` is set
+    Stdout,
+    /// The standard error
+    Stderr,
+}
+
+/// A line of output of the elevated program, passed to the callback of `Command::output_with`
+///
+/// This struct is only available on `Linux`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OutputEvent {
+    /// The stream the line has been written to
+    pub stream: Stream,
+    /// The line, without its trailing newline, which the last line might not have
+    pub line: Vec<u8>,
+    /// When the line has been read
+    pub time: SystemTime,
+}
+
+impl OutputEvent {
+    /// The line as text, with the invalid UTF-8 sequences replaced
+    pub fn text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.line)
+    }
+}
+
+/// Read `pipe` line by line in a new thread, sending each line with its trailing newline
+/// as it arrives, until end of file or until the receiver has gone
+pub(crate) fn read_lines<R: Read + Send + 'static>(
+    pipe: R,
+    stream: Stream,
+    sender: Sender<(Stream, Vec<u8>, SystemTime)>,
+) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = vec![];
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            if sender.send((stream, line, SystemTime::now())).is_err() {
+                return Ok(());
+            }
+        }
+    })
+}
@@ -0,0 +1,127 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::{CancellationHandle, Error, Result};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::process::Child;
+use std::time::Instant;
+use zeroize::Zeroizing;
+
+/// The prompt `sudo -S -p` writes to stderr when it wants the password
+pub(crate) const PROMPT_SENTINEL: &str = "[elevated-command] password:";
+
+/// Written to stderr by the trampoline once `sudo` has authenticated,
+/// right before it is replaced by the elevated program
+pub(crate) const AUTHENTICATED_SENTINEL: &str = "[elevated-command] authenticated";
+
+/// How long stderr is polled for, in milliseconds, before checking the timeout and the cancellation
+const POLL_INTERVAL: libc::c_int = 50;
+
+/// Supplies the password for `Sudo::password`, e.g. from a dialog rendered by the application
+///
+/// It is implemented for closures taking the attempt number
+pub trait PasswordProvider: Send + Sync {
+    /// Ask the user for the password, `attempt` starts from `1` and increases
+    /// after each wrong password
+    ///
+    /// Return `None` if the user cancelled, the returned password is zeroized
+    /// once it has been written to `sudo`
+    fn password(&self, attempt: u32) -> Option<String>;
+}
+
+impl<F> PasswordProvider for F
+where
+    F: Fn(u32) -> Option<String> + Send + Sync,
+{
+    fn password(&self, attempt: u32) -> Option<String> {
+        self(attempt)
+    }
+}
+
+/// Answer the prompts of `sudo -S` on the stderr of `child` with the passwords
+/// of `provider`, until the trampoline reports that the authentication succeeded
+///
+/// `child` is killed and reaped once `deadline` has passed or `cancellation` is cancelled,
+/// which are checked while waiting for `sudo` and once `provider` has returned
+pub(crate) fn authenticate(
+    provider: &dyn PasswordProvider,
+    child: &mut Child,
+    deadline: Option<Instant>,
+    cancellation: Option<&CancellationHandle>,
+) -> Result<()> {
+    let stdin = child.stdin.as_mut().ok_or(Error::Backend("stdin is not piped".to_string()))?;
+    let stderr = child.stderr.as_mut().ok_or(Error::Backend("stderr is not piped".to_string()))?;
+
+    let mut attempt = 0;
+    let mut would_prompt = false;
+    let mut line: Vec<u8> = vec![];
+    let mut byte = [0u8; 1];
+    loop {
+        let limit = match deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            true => Some(Error::TimedOut),
+            false if cancellation.is_some_and(CancellationHandle::is_cancelled) => Some(Error::Cancelled),
+            false => None,
+        };
+        if let Some(err) = limit {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err);
+        }
+        if !poll_readable(stderr.as_raw_fd())? {
+            continue;
+        }
+        // read byte by byte, so that nothing of the elevated program is consumed
+        if stderr.read(&mut byte)? == 0 {
+            let _ = child.wait();
+            return Err(if would_prompt { Error::WouldPrompt } else { Error::NotAuthorized });
+        }
+        if byte[0] == b'\n' {
+            if line == AUTHENTICATED_SENTINEL.as_bytes() {
+                return Ok(());
+            }
+            let message = String::from_utf8_lossy(&line);
+            log::debug!("{}", message);
+            // reported by `sudo -n` when the credentials are not cached
+            would_prompt |= message.contains("a password is required");
+            line.clear();
+            continue;
+        }
+        line.push(byte[0]);
+        if line.ends_with(PROMPT_SENTINEL.as_bytes()) {
+            line.clear();
+            attempt += 1;
+            let password = match provider.password(attempt) {
+                Some(password) => Zeroizing::new(password),
+                None => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(Error::Cancelled);
+                }
+            };
+            // the newline is written separately, as appending it might reallocate
+            // the password and leave a copy of it behind
+            stdin.write_all(password.as_bytes())?;
+            stdin.write_all(b"\n")?;
+            stdin.flush()?;
+        }
+    }
+}
+
+/// Wait for `fd` to be readable or closed for up to `POLL_INTERVAL`
+fn poll_readable(fd: libc::c_int) -> Result<bool> {
+    let mut fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut fd, 1, POLL_INTERVAL) } {
+        ready if ready >= 0 => Ok(ready > 0),
+        _ => match io::Error::last_os_error() {
+            err if err.kind() == io::ErrorKind::Interrupted => Ok(false),
+            err => Err(err.into()),
+        },
+    }
+}
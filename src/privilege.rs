@@ -0,0 +1,80 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use std::fs;
+
+/// The privileges the current process holds, reported by `Command::privilege`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Privilege {
+    /// The real user id
+    pub uid: u32,
+    /// The effective user id, which differs from the real one for setuid programs
+    pub euid: u32,
+    /// The effective capability set, `CapEff` of `/proc/self/status`,
+    /// with a bit set for each capability numbered as in `linux/capability.h`
+    pub capabilities: u64,
+    /// Whether the process runs in a user namespace other than the initial one,
+    /// e.g. in a rootless container, where being root and holding capabilities
+    /// grants nothing over the host
+    pub user_namespace: bool,
+}
+
+impl Privilege {
+    /// The capability to perform most administrative operations
+    pub const CAP_SYS_ADMIN: u32 = 21;
+
+    /// Read the privileges of the current process
+    pub(crate) fn current() -> Self {
+        let status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+        let capabilities = status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+            .unwrap_or(0);
+        Self {
+            uid: unsafe { libc::getuid() },
+            euid: unsafe { libc::geteuid() },
+            capabilities,
+            user_namespace: in_user_namespace(),
+        }
+    }
+
+    /// Whether the process runs as root, be it through a setuid program,
+    /// outside of a user namespace
+    pub fn is_root(&self) -> bool {
+        self.euid == 0 && !self.user_namespace
+    }
+
+    /// Whether the process runs as root inside a user namespace only
+    pub fn is_namespaced_root(&self) -> bool {
+        self.euid == 0 && self.user_namespace
+    }
+
+    /// Whether the effective capability set contains `capability`,
+    /// e.g. `Privilege::CAP_SYS_ADMIN`
+    pub fn has_capability(&self, capability: u32) -> bool {
+        capability < 64 && self.capabilities & (1 << capability) != 0
+    }
+
+    /// Whether the process holds administrative privileges over the host,
+    /// i.e. it runs as root or holds `CAP_SYS_ADMIN`, outside of a user namespace
+    pub fn is_elevated(&self) -> bool {
+        !self.user_namespace && (self.euid == 0 || self.has_capability(Self::CAP_SYS_ADMIN))
+    }
+}
+
+/// Whether the current process runs in a user namespace other than the initial one,
+/// whose `uid_map` maps the whole range of ids onto itself
+fn in_user_namespace() -> bool {
+    match fs::read_to_string("/proc/self/uid_map") {
+        Ok(map) => {
+            let ranges: Vec<Vec<&str>> = map.lines().map(|line| line.split_whitespace().collect()).collect();
+            ranges != [vec!["0", "0", "4294967295"]]
+        }
+        // user namespaces are not supported by the kernel
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,308 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::Result;
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::Command as StdCommand;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// The size given to a new pseudo-terminal when the current process has no terminal
+const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// The character ending the input on a terminal, i.e. Ctrl-D
+pub(crate) const END_OF_FILE: u8 = 0x04;
+
+/// How long the stdin of the current process is polled for, in milliseconds,
+/// before checking whether its input is still forwarded
+const POLL_INTERVAL: libc::c_int = 50;
+
+/// The master side of the pseudo-terminal an elevated program is attached to,
+/// see `Command::pty`
+///
+/// Reading returns what the program writes to the terminal, both its standard output
+/// and standard error, and writing sends it input as if typed on a terminal, e.g.
+/// `\x03` for Ctrl-C. Reading returns end of file once the program and all the other
+/// processes attached to the terminal have exited
+///
+/// This struct is only available on `Linux`
+///
+/// # Examples
+///
+/// ```
+/// use elevated_command::Command;
+/// use elevated_command::backend::Direct;
+/// use std::io::{Read, Write};
+/// use std::process::Command as StdCommand;
+///
+/// fn main() {
+///     let mut cmd = StdCommand::new("sh");
+///     cmd.args(["-c", "read name; [ -t 1 ] && echo \"hello $name\"; stty size"]);
+///     let mut elevated_cmd = Command::new(cmd);
+///     elevated_cmd.backend(Direct::new()).pty(true);
+///     let mut child = elevated_cmd.spawn().unwrap();
+///
+///     let mut pty = child.pty.take().unwrap();
+///     pty.resize(40, 120).unwrap();
+///     pty.write_all(b"root\n").unwrap();
+///     let mut output = String::new();
+///     pty.read_to_string(&mut output).unwrap();
+///     // the input is echoed by the terminal
+///     assert_eq!(output, "root\r\nhello root\r\n40 120\r\n");
+///     assert!(child.wait().unwrap().success());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Pty {
+    master: File,
+}
+
+impl Pty {
+    /// Allocate a pseudo-terminal, return its master side and its slave side,
+    /// which is to be attached to the backend by `Pty::attach`
+    pub(crate) fn open() -> Result<(Self, File)> {
+        let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if master < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let master = unsafe { File::from_raw_fd(master) };
+        let fd = master.as_raw_fd();
+        let mut name = [0 as libc::c_char; 64];
+        if unsafe { libc::grantpt(fd) } != 0
+            || unsafe { libc::unlockpt(fd) } != 0
+            || unsafe { libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) } != 0
+        {
+            return Err(io::Error::last_os_error().into());
+        }
+        let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+        let slave = unsafe { libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+        if slave < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let slave = unsafe { File::from_raw_fd(slave) };
+
+        let pty = Self { master };
+        let (rows, cols) = terminal_size().unwrap_or(DEFAULT_SIZE);
+        pty.resize(rows, cols)?;
+        Ok((pty, slave))
+    }
+
+    /// Make the slave side the controlling terminal of the backend, in a new session,
+    /// once it has been set as its stdio
+    pub(crate) fn attach(command: &mut StdCommand) {
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Resize the terminal to `rows` by `cols`, the elevated program is sent `SIGWINCH`
+    pub fn resize(&self, rows: u16, cols: u16) -> Result<()> {
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    /// The size of the terminal, in rows and columns
+    ///
+    /// It is initially the size of the terminal of the current process,
+    /// or 24 rows by 80 columns if it has none
+    pub fn size(&self) -> Result<(u16, u16)> {
+        window_size(self.master.as_raw_fd()).map_err(Into::into)
+    }
+
+    /// Creates a new handle to the same terminal, e.g. to write to it
+    /// from another thread than the one reading from it
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            master: self.master.try_clone()?,
+        })
+    }
+}
+
+impl Read for Pty {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.master.read(buf) {
+            // the slave side has been closed by all the processes attached to it
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+            result => result,
+        }
+    }
+}
+
+impl Write for Pty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.master.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master.flush()
+    }
+}
+
+impl AsFd for Pty {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.master.as_fd()
+    }
+}
+
+impl AsRawFd for Pty {
+    fn as_raw_fd(&self) -> RawFd {
+        self.master.as_raw_fd()
+    }
+}
+
+/// The terminal of the current process switched to raw mode, so that the keys are passed
+/// as typed to the pseudo-terminal, which echoes and interprets them, e.g. Ctrl-C.
+/// The previous mode is restored when it is dropped
+pub(crate) struct RawMode {
+    previous: libc::termios,
+}
+
+impl RawMode {
+    /// Switch the terminal of stdin to raw mode, `None` if stdin is not a terminal
+    pub fn enable() -> Option<Self> {
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return None;
+        }
+        let previous = termios;
+        unsafe { libc::cfmakeraw(&mut termios) };
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return None;
+        }
+        Some(Self { previous })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.previous) };
+    }
+}
+
+/// Set by the handler of `SIGWINCH` installed by `ResizeRelay`
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// A handler of `SIGWINCH`, so that the resizes of the terminal of the current process
+/// are applied to the pseudo-terminal by `forward_input`. The previous handler is restored
+/// when it is dropped
+pub(crate) struct ResizeRelay {
+    previous: libc::sigaction,
+}
+
+impl ResizeRelay {
+    /// Install the handler, `None` if it could not be installed
+    pub fn install() -> Option<Self> {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = on_resize as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        if unsafe { libc::sigaction(libc::SIGWINCH, &action, &mut previous) } != 0 {
+            return None;
+        }
+        Some(Self { previous })
+    }
+}
+
+impl Drop for ResizeRelay {
+    fn drop(&mut self) {
+        unsafe { libc::sigaction(libc::SIGWINCH, &self.previous, std::ptr::null_mut()) };
+    }
+}
+
+/// Copy the stdin of the current process to `pty` in a new thread until `done` is set,
+/// ending the input of the program once stdin is closed, and resize `pty` along with
+/// the terminal of the current process once `ResizeRelay` has reported it
+///
+/// Stdin is polled and read directly rather than through std::io::Stdin, whose lock
+/// would otherwise be held, and its next input consumed, once `done` is set
+pub(crate) fn forward_input(mut pty: Pty, done: Arc<AtomicBool>) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        while !done.load(Ordering::Relaxed) {
+            if RESIZED.swap(false, Ordering::Relaxed) {
+                if let Some((rows, cols)) = terminal_size() {
+                    let _ = pty.resize(rows, cols);
+                }
+            }
+            let mut fd = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut fd, 1, POLL_INTERVAL) };
+            if ready <= 0 {
+                match io::Error::last_os_error() {
+                    err if ready < 0 && err.kind() != io::ErrorKind::Interrupted => return Err(err),
+                    _ => continue,
+                }
+            }
+            let read = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            match read {
+                0 => return pty.write_all(&[END_OF_FILE]),
+                read if read < 0 => match io::Error::last_os_error() {
+                    err if err.kind() == io::ErrorKind::Interrupted => continue,
+                    err => return Err(err),
+                },
+                read => pty.write_all(&buf[..read as usize])?,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Copy what is written to `pty` to the stdout of the current process in a new thread,
+/// flushing it as it arrives, as prompts are not terminated by a newline
+pub(crate) fn forward_output(mut pty: Pty) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut stdout = io::stdout();
+        let mut buf = [0u8; 4096];
+        loop {
+            let read = pty.read(&mut buf)?;
+            if read == 0 {
+                return Ok(());
+            }
+            stdout.write_all(&buf[..read])?;
+            stdout.flush()?;
+        }
+    })
+}
+
+/// The size of the terminal of the current process, if any
+fn terminal_size() -> Option<(u16, u16)> {
+    [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .into_iter()
+        .filter_map(|fd| window_size(fd).ok())
+        .find(|&(rows, cols)| rows > 0 && cols > 0)
+}
+
+fn window_size(fd: RawFd) -> io::Result<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((size.ws_row, size.ws_col))
+}
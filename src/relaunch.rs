@@ -0,0 +1,213 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::{Command, Error, Result};
+use std::env;
+use std::process::{self, Command as StdCommand};
+
+/// Set on the relaunched process, so that it does not relaunch itself again
+/// if it still is not elevated
+#[cfg(not(target_os = "windows"))]
+const MARKER: &str = "ELEVATED_COMMAND_RELAUNCHED";
+
+/// Appended to the arguments of the relaunched process on Windows, as `ShellExecuteExW`
+/// does not pass the environment of the current process on
+#[cfg(target_os = "windows")]
+const MARKER: &str = "--elevated-command-relaunched";
+
+/// Relaunch the current executable with escalated privileges and the same arguments
+/// and working directory, unless it is already elevated
+///
+/// Return `Ok(())` right away if the current process is elevated, so that it
+/// carries on. Otherwise, the user is prompted, the relaunched process is waited for
+/// and the current process exits with its exit code, this function then never returns.
+/// An error is returned if the elevation failed, or if the current process has been
+/// relaunched already but still is not elevated, to avoid relaunching in a loop
+///
+/// The relaunched process is marked through the environment variable
+/// `ELEVATED_COMMAND_RELAUNCHED`, and only gets the environment variables
+/// of the default `EnvPolicy` besides it. On Windows, it is marked through
+/// the argument `--elevated-command-relaunched` appended to its arguments instead,
+/// which the program has to ignore
+///
+/// On Linux, the relaunched process shares the stdio of the current one, and
+/// is reported with the exit code `128 + signal` if it is killed by a signal.
+/// On MacOS, its output is written out once it has exited. On Windows, it is started
+/// through `ShellExecuteExW` in a window of its own, and waited for
+///
+/// # Examples
+///
+/// ```no_run
+/// use elevated_command::elevate_self;
+///
+/// fn main() {
+///     elevate_self().unwrap();
+///     // from here on, the program runs elevated
+/// }
+/// ```
+pub fn elevate_self() -> Result<()> {
+    if !needs_relaunch(Command::is_elevated(), is_relaunched())? {
+        return Ok(());
+    }
+
+    let mut cmd = StdCommand::new(env::current_exe()?);
+    cmd.args(env::args_os().skip(1));
+    mark(&mut cmd);
+    if let Ok(dir) = env::current_dir() {
+        cmd.current_dir(dir);
+    }
+    let code = relaunch(Command::new(cmd))?;
+    process::exit(code)
+}
+
+/// Whether the current process has to be relaunched, which is an error if it has been
+/// relaunched already but still is not elevated
+fn needs_relaunch(elevated: bool, relaunched: bool) -> Result<bool> {
+    match (elevated, relaunched) {
+        (true, _) => Ok(false),
+        (false, true) => Err(Error::Backend("the relaunched process is not elevated".to_string())),
+        (false, false) => Ok(true),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn is_relaunched() -> bool {
+    env::var_os(MARKER).is_some()
+}
+
+#[cfg(target_os = "windows")]
+fn is_relaunched() -> bool {
+    env::args_os().skip(1).any(|arg| arg == MARKER)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn mark(cmd: &mut StdCommand) {
+    cmd.env(MARKER, "1");
+}
+
+#[cfg(target_os = "windows")]
+fn mark(cmd: &mut StdCommand) {
+    cmd.arg(MARKER);
+}
+
+#[cfg(target_os = "linux")]
+fn relaunch(mut cmd: Command) -> Result<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let status = cmd.status()?;
+    Ok(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)))
+}
+
+#[cfg(target_os = "macos")]
+fn relaunch(cmd: Command) -> Result<i32> {
+    use std::io::{self, Write};
+
+    let output = cmd.output()?;
+    io::stdout().write_all(&output.stdout)?;
+    io::stderr().write_all(&output.stderr)?;
+    Ok(output.status.code().unwrap_or(1))
+}
+
+#[cfg(target_os = "windows")]
+fn relaunch(cmd: Command) -> Result<i32> {
+    use std::mem;
+    use windows::core::{w, HSTRING, PCWSTR};
+    use windows::Win32::Foundation::{CloseHandle, ERROR_CANCELLED};
+    use windows::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject, INFINITE};
+    use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
+    use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+    let program = HSTRING::from(cmd.cmd.get_program());
+    let parameters = HSTRING::from(parameters(&cmd));
+    let dir = cmd.cmd.get_current_dir().map(|dir| HSTRING::from(dir.as_os_str()));
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+        // keep the handle to the process to wait for it
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(program.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        lpDirectory: dir.as_ref().map_or(PCWSTR::null(), |dir| PCWSTR(dir.as_ptr())),
+        nShow: SW_SHOWNORMAL.0,
+        ..Default::default()
+    };
+    if let Err(err) = unsafe { ShellExecuteExW(&mut info) } {
+        // the user declined the UAC prompt
+        if err.code() == ERROR_CANCELLED.to_hresult() {
+            return Err(Error::Cancelled);
+        }
+        return Err(Error::Backend(format!("ShellExecuteExW failed: {}", err)));
+    }
+    if info.hProcess.is_invalid() {
+        return Err(Error::Backend("ShellExecuteExW started no process".to_string()));
+    }
+
+    let mut code = 0u32;
+    let exited = unsafe {
+        WaitForSingleObject(info.hProcess, INFINITE);
+        GetExitCodeProcess(info.hProcess, &mut code)
+    };
+    let _ = unsafe { CloseHandle(info.hProcess) };
+    exited.map_err(|err| Error::Backend(format!("GetExitCodeProcess failed: {}", err)))?;
+    Ok(code as i32)
+}
+
+/// The command line of the relaunched process, without the program
+#[cfg(target_os = "windows")]
+fn parameters(cmd: &Command) -> String {
+    let args: Vec<String> = cmd.cmd.get_args().map(|arg| quote(&arg.to_string_lossy())).collect();
+    args.join(" ")
+}
+
+/// Quote `arg` for the command line parsed by the relaunched process,
+/// following the rules of `CommandLineToArgvW`
+#[cfg(target_os = "windows")]
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            // the backslashes preceding a quote are escaped, and so is the quote
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes + 1));
+                backslashes = 0;
+            }
+            _ => backslashes = 0,
+        }
+        quoted.push(c);
+    }
+    // the backslashes preceding the closing quote are escaped
+    quoted.push_str(&"\\".repeat(backslashes));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relaunches_only_once() {
+        assert!(matches!(needs_relaunch(true, false), Ok(false)));
+        assert!(matches!(needs_relaunch(true, true), Ok(false)));
+        assert!(matches!(needs_relaunch(false, false), Ok(true)));
+        assert!(matches!(needs_relaunch(false, true), Err(Error::Backend(_))));
+    }
+
+    #[test]
+    fn marks_the_relaunched_process() {
+        let mut cmd = StdCommand::new("program");
+        cmd.arg("argument");
+        mark(&mut cmd);
+        #[cfg(not(target_os = "windows"))]
+        assert!(cmd.get_envs().any(|(k, v)| k == MARKER && v.is_some()));
+        #[cfg(target_os = "windows")]
+        assert_eq!(parameters(&Command::new(cmd)), format!("argument {}", MARKER));
+    }
+}
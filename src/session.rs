@@ -0,0 +1,388 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::askpass::private_dir;
+use crate::backend::{self, target_args, Handoff};
+use crate::{Backend, Command, ElevatedChild, Error, PromptMode, Result};
+use std::ffi::{CString, OsStr};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ChildStdin, ChildStdout, Command as StdCommand, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The helper elevated once by the session, it reports that it is ready,
+/// then evaluates each line written to its stdin, which runs a command and
+/// reports its exit code. Newlines of the arguments are passed as `$nl`,
+/// so that each command fits in one line
+const HELPER: &str = r#"nl='
+'
+echo ready
+while IFS= read -r line; do eval "$line"; done"#;
+
+/// The idle timeout of a session, unless set by `ElevatedSession::idle_timeout`
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long the output pipes are polled for, in milliseconds, before checking
+/// whether the helper is still running
+const POLL_INTERVAL: libc::c_int = 50;
+
+/// Elevate a helper process once, then execute any number of commands through it,
+/// so that the user is prompted once for all of them
+///
+/// The helper is a shell running as root, which receives the commands over a pipe
+/// and sends their output back through named pipes in a directory private to
+/// the current user. It exits when the session is closed, explicitly by
+/// `ElevatedSession::close`, when the session is dropped, or after it has been
+/// idle for longer than the idle timeout, in which case the next command opens
+/// the session again and prompts again
+///
+/// This struct is only available on `Linux`
+///
+/// # Examples
+///
+/// ```
+/// use elevated_command::{Command, ElevatedSession};
+/// use elevated_command::backend::Direct;
+/// use std::process::Command as StdCommand;
+///
+/// fn main() {
+///     let mut session = ElevatedSession::new();
+///     // spawn the helper unprivileged, without prompting
+///     session.backend(Direct::new());
+///     session.open().unwrap();
+///     let helper = session.id();
+///
+///     let mut cmd = StdCommand::new("printf");
+///     cmd.arg("it's\n%s").arg("multi\nline");
+///     let output = session.output(&Command::new(cmd)).unwrap();
+///     assert_eq!(output.stdout, b"it's\nmulti\nline");
+///
+///     let mut cmd = StdCommand::new("sh");
+///     cmd.args(["-c", "echo failed >&2; exit 3"]);
+///     let output = session.output(&Command::new(cmd)).unwrap();
+///     assert_eq!(output.status.code(), Some(3));
+///     assert_eq!(output.stderr, b"failed\n");
+///
+///     // both commands ran through the same helper
+///     assert_eq!(session.id(), helper);
+///     session.close().unwrap();
+///     assert!(!session.is_open());
+/// }
+/// ```
+pub struct ElevatedSession {
+    backend: Option<Arc<dyn Backend>>,
+    prompt_mode: PromptMode,
+    idle_timeout: Duration,
+    state: Arc<(Mutex<Option<Helper>>, Condvar)>,
+}
+
+/// The running helper and the pipes to it
+struct Helper {
+    child: ElevatedChild,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    dir: PathBuf,
+    last_used: Instant,
+}
+
+impl ElevatedSession {
+    /// Constructs a new `ElevatedSession`, the helper is elevated
+    /// by `ElevatedSession::open` or by the first command
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            prompt_mode: PromptMode::default(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            state: Arc::new((Mutex::new(None), Condvar::new())),
+        }
+    }
+
+    /// Set the backend elevating the helper, see `Command::backend`
+    pub fn backend<B: Backend + 'static>(&mut self, backend: B) -> &mut Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Set where the user is prompted when the helper is elevated, see `Command::prompt_mode`
+    pub fn prompt_mode(&mut self, mode: PromptMode) -> &mut Self {
+        self.prompt_mode = mode;
+        self
+    }
+
+    /// Set how long the helper may stay idle before it exits, 5 minutes by default
+    pub fn idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Whether the helper is running
+    pub fn is_open(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Returns the OS-assigned process identifier of the backend process
+    /// of the helper, if it is running
+    pub fn id(&self) -> Option<u32> {
+        self.lock().as_ref().map(|helper| helper.child.id())
+    }
+
+    /// Elevate the helper, prompting the user, unless it is already running
+    pub fn open(&mut self) -> Result<()> {
+        let mut helper = self.lock();
+        if helper.is_none() {
+            *helper = Some(self.spawn()?);
+            self.watch();
+        }
+        Ok(())
+    }
+
+    /// Execute `cmd` through the helper, opening the session if necessary,
+    /// and return its output
+    ///
+    /// The program, the arguments, the environment variables and the working
+    /// directory of `cmd` are honored, while its backend, prompt mode, target user
+    /// and stdio are not, as the helper has already been elevated. The stdin of
+    /// the command is `/dev/null`
+    ///
+    /// A command killed by a signal is reported with the exit code `128 + signal`
+    pub fn output(&mut self, cmd: &Command) -> Result<Output> {
+        self.open()?;
+        let mut guard = self.lock();
+        let helper = guard.as_mut().ok_or(Error::Backend("the helper is not running".to_string()))?;
+        let output = helper.run(cmd);
+        helper.last_used = Instant::now();
+        match output {
+            // the helper died, so that the next command opens the session again
+            Err(Error::Backend(message)) => {
+                if let Some(helper) = guard.take() {
+                    let _ = helper.close();
+                }
+                Err(Error::Backend(message))
+            }
+            output => output,
+        }
+    }
+
+    /// Close the session, the helper exits and the next command would prompt again
+    pub fn close(&mut self) -> Result<()> {
+        let helper = self.lock().take();
+        self.state.1.notify_all();
+        match helper {
+            Some(helper) => helper.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Helper>> {
+        self.state.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn spawn(&self) -> Result<Helper> {
+        let mut helper_cmd = StdCommand::new("/bin/sh");
+        helper_cmd.args(["-c", HELPER]);
+        let mut cmd = Command::new(helper_cmd);
+        cmd.backend = self.backend.clone();
+        cmd.prompt_mode = self.prompt_mode;
+        let mut child = cmd.launch(Stdio::piped(), Stdio::piped(), Stdio::inherit())?;
+
+        let stdin = child.stdin.take().ok_or(Error::Backend("stdin is not piped".to_string()))?;
+        let stdout = child.stdout.take().ok_or(Error::Backend("stdout is not piped".to_string()))?;
+        let mut stdout = BufReader::new(stdout);
+        let mut line = String::new();
+        stdout.read_line(&mut line)?;
+        if line != "ready\n" {
+            // the backend exited before running the helper, e.g. the user cancelled
+            drop(stdin);
+            let status = child.wait()?;
+            return Err(Error::Backend(format!("the helper did not start: {}", status)));
+        }
+
+        static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+        let count = SESSIONS.fetch_add(1, Ordering::Relaxed);
+        let dir = private_dir()?.join(format!("session-{}-{}", std::process::id(), count));
+        fs::create_dir(&dir)?;
+        Ok(Helper {
+            child,
+            stdin,
+            stdout,
+            dir,
+            last_used: Instant::now(),
+        })
+    }
+
+    /// Close the helper once it has been idle for longer than the idle timeout
+    fn watch(&self) {
+        let state = self.state.clone();
+        let timeout = self.idle_timeout;
+        thread::spawn(move || {
+            let (helper, idle) = &*state;
+            let mut guard = helper.lock().unwrap_or_else(|err| err.into_inner());
+            loop {
+                let elapsed = match guard.as_ref() {
+                    Some(helper) => helper.last_used.elapsed(),
+                    None => return,
+                };
+                if elapsed >= timeout {
+                    if let Some(helper) = guard.take() {
+                        log::debug!("Closing the idle elevated session");
+                        let _ = helper.close();
+                    }
+                    return;
+                }
+                guard = idle
+                    .wait_timeout(guard, timeout - elapsed)
+                    .map(|(guard, _)| guard)
+                    .unwrap_or_else(|err| err.into_inner().0);
+            }
+        });
+    }
+}
+
+impl Default for ElevatedSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ElevatedSession {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+impl Helper {
+    fn run(&mut self, cmd: &Command) -> Result<Output> {
+        let out = self.dir.join("out");
+        let err = self.dir.join("err");
+        make_fifo(&out)?;
+        make_fifo(&err)?;
+        let output = self.exchange(cmd, &out, &err);
+        let _ = fs::remove_file(&out);
+        let _ = fs::remove_file(&err);
+        output
+    }
+
+    fn exchange(&mut self, cmd: &Command, out: &Path, err: &Path) -> Result<Output> {
+        // the environment file of the command is removed once its output has been read
+        let mut handoff = Handoff::new();
+        let mut line = vec![];
+        for arg in target_args(cmd, &mut handoff)? {
+            line.extend(quote(&arg));
+            line.push(b' ');
+        }
+        line.extend(b"</dev/null >");
+        line.extend(quote(out.as_os_str()));
+        line.extend(b" 2>");
+        line.extend(quote(err.as_os_str()));
+        line.extend(b"; echo \"$?\"\n");
+        if self.stdin.write_all(&line).and_then(|_| self.stdin.flush()).is_err() {
+            return Err(Error::Backend("the helper exited".to_string()));
+        }
+
+        let (stdout, stderr) = self.read_output(out, err)?;
+
+        let mut code = String::new();
+        self.stdout.read_line(&mut code)?;
+        let code: i32 = code
+            .trim()
+            .parse()
+            .map_err(|_| Error::Backend("the helper exited".to_string()))?;
+        let output = Output {
+            status: ExitStatus::from_raw((code & 0xff) << 8),
+            stdout,
+            stderr,
+        };
+        match backend::chdir_failure(&output) {
+            Some(err) => Err(err),
+            None => Ok(output),
+        }
+    }
+
+    /// Read the output of the command from the named pipes until the command has closed them
+    ///
+    /// The pipes are opened without blocking, as the helper might never open them
+    /// if it has died, which is checked whenever they stay idle
+    fn read_output(&mut self, out: &Path, err: &Path) -> Result<(Vec<u8>, Vec<u8>)> {
+        let open = |path: &Path| OpenOptions::new().read(true).custom_flags(libc::O_NONBLOCK).open(path);
+        let mut pipes = [(open(out)?, vec![], false), (open(err)?, vec![], false)];
+        let mut buf = [0u8; 4096];
+        while pipes.iter().any(|(_, _, closed)| !closed) {
+            // a pipe only reports that it has been closed once the helper has opened it
+            let mut fds: Vec<libc::pollfd> = pipes
+                .iter()
+                .map(|(pipe, _, closed)| libc::pollfd {
+                    fd: if *closed { -1 } else { pipe.as_raw_fd() },
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, POLL_INTERVAL) };
+            if ready < 0 {
+                match io::Error::last_os_error() {
+                    err if err.kind() == io::ErrorKind::Interrupted => continue,
+                    err => return Err(err.into()),
+                }
+            }
+            if ready == 0 {
+                if !matches!(self.child.try_wait(), Ok(None)) {
+                    return Err(Error::Backend("the helper exited".to_string()));
+                }
+                continue;
+            }
+            for ((pipe, output, closed), fd) in pipes.iter_mut().zip(fds) {
+                if fd.revents == 0 {
+                    continue;
+                }
+                match pipe.read(&mut buf) {
+                    Ok(0) => *closed = true,
+                    Ok(read) => output.extend(&buf[..read]),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+        let [(_, stdout, _), (_, stderr, _)] = pipes;
+        Ok((stdout, stderr))
+    }
+
+    fn close(mut self) -> Result<()> {
+        drop(self.stdin);
+        let _ = fs::remove_dir_all(&self.dir);
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+/// Quote `arg` for the shell, within single quotes but for the quotes
+/// themselves and the newlines, which are passed through `$nl`
+fn quote(arg: &OsStr) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for byte in arg.as_bytes() {
+        match byte {
+            b'\'' => quoted.extend(b"'\\''"),
+            b'\n' => quoted.extend(b"'\"$nl\"'"),
+            byte => quoted.push(*byte),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+fn make_fifo(path: &Path) -> Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::InvalidArgument("path contains a nul byte".to_string()))?;
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
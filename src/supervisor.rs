@@ -0,0 +1,155 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (c) Luis Liu. All rights reserved.
+ *  Licensed under the MIT License. See License in the project root for license information.
+ *--------------------------------------------------------------------------------------------*/
+
+use crate::askpass::private_dir;
+use crate::{Error, Result};
+use std::ffi::{CString, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::mem::ManuallyDrop;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Runs as root in front of the elevated program, which it starts in its own process
+/// group. If it is attached to a terminal, job control hands the terminal to the group,
+/// otherwise the group is started in a new session. It relays `SIGINT`, `SIGTERM` and
+/// `SIGHUP` it receives, and the signals whose names are written to its control pipe by
+/// the parent, to the group. When the parent closes the control pipe or dies, the group
+/// is sent `SIGTERM`, then `SIGKILL`. It exits with the exit code of the elevated program,
+/// or kills itself with the signal which killed it, so that the backend reports it as is
+const SUPERVISOR: &str = r#"control=$1; shift
+exec 3<"$control"
+rm -f -- "$control"
+# without job control, the stdin of an asynchronous command is /dev/null
+# before its redirections, and SIGINT and SIGQUIT are ignored
+exec 4<&0
+[ -t 0 ] && set -m 2>/dev/null
+case $- in
+*m*) "$@" 3<&- 4<&- & pid=$!; target=-$pid; resume='fg %1' ;;
+*) setsid "$@" 3<&- <&4 4<&- & pid=$!; target=-$pid; resume='wait $pid' ;;
+esac
+trap 'kill -s INT -- "$target" 2>/dev/null' INT
+trap 'kill -s HUP -- "$target" 2>/dev/null' HUP
+trap 'kill -s TERM -- "$target" 2>/dev/null' TERM
+(
+    while read -r signal <&3; do kill -s "$signal" -- "$target" 2>/dev/null; done
+    kill -s TERM -- "$target" 2>/dev/null; sleep 2; kill -s KILL -- "$target" 2>/dev/null
+) 4<&- </dev/null >/dev/null 2>&1 &
+relay=$!
+exec 3<&- 4<&-
+while :; do
+    eval "$resume" >/dev/null 2>&1; status=$?
+    if [ "$status" -gt 128 ] && kill -0 "$pid" 2>/dev/null; then continue; fi
+    break
+done
+kill "$relay" 2>/dev/null
+if [ "$status" -gt 128 ]; then
+    trap - INT HUP TERM
+    kill -s "$(kill -l "$status")" "$$" 2>/dev/null
+fi
+exit "$status""#;
+
+/// The signals which could be relayed through the control pipe, by number and name
+const SIGNALS: [(libc::c_int, &str); 10] = [
+    (libc::SIGHUP, "HUP"),
+    (libc::SIGINT, "INT"),
+    (libc::SIGQUIT, "QUIT"),
+    (libc::SIGKILL, "KILL"),
+    (libc::SIGUSR1, "USR1"),
+    (libc::SIGUSR2, "USR2"),
+    (libc::SIGTERM, "TERM"),
+    (libc::SIGCONT, "CONT"),
+    (libc::SIGSTOP, "STOP"),
+    (libc::SIGTSTP, "TSTP"),
+];
+
+/// The write end of the control pipe of a supervisor
+///
+/// It is a named pipe in the directory private to the current user, opened for
+/// both reading and writing so that opening it never blocks and writing to it
+/// never fails, whether the supervisor has started or exited
+///
+/// Dropping the control closes the pipe, which tears the elevated program down,
+/// unless `Control::keep_open` is called once the backend has been launched
+pub(crate) struct Control {
+    file: ManuallyDrop<File>,
+    path: PathBuf,
+    keep_open: bool,
+}
+
+impl Control {
+    /// Create the control pipe
+    pub fn new() -> Result<Self> {
+        static PIPES: AtomicUsize = AtomicUsize::new(0);
+        let count = PIPES.fetch_add(1, Ordering::Relaxed);
+        let path = private_dir()?.join(format!("control-{}-{}", std::process::id(), count));
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| Error::InvalidArgument("path contains a nul byte".to_string()))?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let file = match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                let _ = fs::remove_file(&path);
+                return Err(err.into());
+            }
+        };
+        Ok(Self {
+            file: ManuallyDrop::new(file),
+            path,
+            keep_open: false,
+        })
+    }
+
+    /// The path to the control pipe
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep the pipe open when the control is dropped, so that the elevated program
+    /// keeps running until the current process exits
+    pub fn keep_open(&mut self) {
+        self.keep_open = true;
+    }
+
+    /// Ask the supervisor to send `signal` to the elevated program
+    pub fn send(&mut self, signal: libc::c_int) -> Result<()> {
+        let name = SIGNALS
+            .iter()
+            .find(|(number, _)| *number == signal)
+            .map(|(_, name)| *name)
+            .ok_or_else(|| Error::InvalidArgument(format!("signal {} cannot be relayed", signal)))?;
+        writeln!(*self.file, "{}", name)?;
+        Ok(())
+    }
+
+    /// Close the pipe once the elevated program has exited
+    pub fn close(mut self) {
+        self.keep_open = false;
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        if !self.keep_open {
+            // the supervisor removes the pipe once it has opened it,
+            // which it never does if the elevation failed
+            let _ = fs::remove_file(&self.path);
+            unsafe { ManuallyDrop::drop(&mut self.file) };
+        }
+    }
+}
+
+/// Prepend the supervisor controlled through `control` to `args`
+pub(crate) fn supervise(control: &Path, args: Vec<OsString>) -> Vec<OsString> {
+    let mut supervised: Vec<OsString> = ["/bin/sh", "-c", SUPERVISOR, "elevated-command"]
+        .map(OsString::from)
+        .to_vec();
+    supervised.push(control.as_os_str().to_os_string());
+    supervised.extend(args);
+    supervised
+}
@@ -3,8 +3,7 @@
  *  Licensed under the MIT License. See License in the project root for license information.
  *--------------------------------------------------------------------------------------------*/
 
-use crate::Command;
-use anyhow::Result;
+use crate::{Command, Error, Result};
 use std::mem;
 use std::os::windows::process::ExitStatusExt;
 use std::process::{Output, ExitStatus};
@@ -14,7 +13,7 @@ use winapi::um::securitybaseapi::GetTokenInformation;
 use winapi::um::winnt::{HANDLE, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
 use windows::core::{HSTRING, PCWSTR, w};
 use windows::Win32::Foundation::HWND;
-use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::Shell::{ShellExecuteW, SE_ERR_ACCESSDENIED};
 use windows::Win32::UI::WindowsAndMessaging::SW_HIDE;
 
 
@@ -65,7 +64,8 @@ impl Command {
     /// 
     /// On Windows, according to https://learn.microsoft.com/en-us/windows/win32/api/shellapi/nf-shellapi-shellexecutew#return-value,
     /// Output.status.code() shoudl be greater than 32 if the function succeeds, 
-    /// otherwise the value indicates the cause of the failure
+    /// otherwise the value indicates the cause of the failure.
+    /// `Error::Cancelled` is returned instead if the user declined the UAC prompt
     /// 
     /// On Windows, Output.stdout and Output.stderr will always be empty as of now 
     /// 
@@ -104,6 +104,9 @@ impl Command {
                 SW_HIDE
             ) 
         };
+        if r.0 as u32 == SE_ERR_ACCESSDENIED {
+            return Err(Error::Cancelled);
+        }
         Ok(Output {
             status: ExitStatus::from_raw(r.0 as u32),
             stdout: Vec::<u8>::new(),